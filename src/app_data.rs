@@ -1,18 +1,32 @@
 use crate::config::Config;
 use crate::error::Error;
-use crate::model::{Issuer, Subject};
-use crate::store::{AudiencesStore, AuthorizationsStore, CustomClaimsStore, JwksStore, UserInfoStore};
+use crate::model::{decode_with_secret, is_symmetric_alg, Claims, Issuer, Subject};
+use crate::store::{
+    ActionsStore, AudiencesStore, AuthorizationsStore, ClientsStore, CustomClaimsStore, JwksStore, RefreshTokensStore,
+    RolesStore, UserInfoStore, UsersStore,
+};
 use derive_getters::Getters;
+use jsonwebtoken::Algorithm;
 
 #[derive(Getters)]
 pub struct AppData {
     issuer: Issuer,
     subject: Subject,
+    access_token_lifetime: i64,
+    id_token_lifetime: i64,
+    /// Client of the enrichment webhooks
+    http_client: reqwest::Client,
+    actions: ActionsStore,
     audiences: AudiencesStore,
     authorizations: AuthorizationsStore,
+    clients: ClientsStore,
     custom_claims: CustomClaimsStore,
     jwks: JwksStore,
+    refresh_tokens: RefreshTokensStore,
+    roles: RolesStore,
+    roles_claim: Option<String>,
     user_info: UserInfoStore,
+    users: UsersStore,
 }
 
 impl AppData {
@@ -20,11 +34,48 @@ impl AppData {
         Ok(Self {
             issuer: config.issuer().clone(),
             subject: config.subject().clone(),
+            access_token_lifetime: *config.access_token().lifetime(),
+            id_token_lifetime: *config.id_token().lifetime(),
+            http_client: reqwest::Client::new(),
+            actions: ActionsStore::new(config.action())?,
             audiences: AudiencesStore::new(config.audience()),
-            authorizations: AuthorizationsStore::default(),
+            authorizations: AuthorizationsStore::new(config.authorization_code()),
+            clients: ClientsStore::new(config.client()),
             custom_claims: CustomClaimsStore::new(config.access_token()),
-            jwks: JwksStore::new()?,
+            jwks: JwksStore::new(config.jwks(), signing_algs(config))?,
+            refresh_tokens: RefreshTokensStore::new(config.refresh_token()),
+            roles: RolesStore::new(config.role()),
+            roles_claim: config.access_token().roles_claim().clone(),
             user_info: UserInfoStore::new(config.user_info().into()),
+            users: UsersStore::new(config.user()),
         })
     }
+
+    /// Verifies an access token issued for the given audience: with its secret when it signs with an HS algorithm,
+    /// otherwise with the published keys
+    pub fn verify_access_token(&self, token: &str, audience: &str) -> Result<Claims, Error> {
+        match self.audiences.get(audience)? {
+            Some(settings) if is_symmetric_alg(settings.signing_alg()) => match settings.signing_secret() {
+                Some(secret) => decode_with_secret(token, settings.signing_alg(), secret, audience),
+                None => Err(Error::InvalidToken(format!(
+                    "Audience '{audience}' has no signing secret"
+                ))),
+            },
+            _ => self.jwks.get()?.parse(token, &[audience]),
+        }
+        .map_err(|error| Error::InvalidToken(error.to_string()))
+    }
+}
+
+/// Algorithms of the `[jwks]` section followed by the asymmetric ones the audiences sign with
+fn signing_algs(config: &Config) -> Vec<Algorithm> {
+    let mut signing_algs: Vec<Algorithm> = config.jwks().signing_algs().clone();
+
+    for alg in config.audience().iter().map(|audience| *audience.signing_alg()) {
+        if !is_symmetric_alg(alg) && !signing_algs.contains(&alg) {
+            signing_algs.push(alg);
+        }
+    }
+
+    signing_algs
 }
@@ -1,12 +1,20 @@
+use std::collections::HashMap;
 use std::fs;
+use std::num::NonZeroU64;
+use std::path::PathBuf;
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, TimeDelta, Utc};
 use derive_getters::Getters;
+use jsonwebtoken::Algorithm;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 use thiserror::Error;
 
-use crate::model::{defaults, Issuer, Subject};
+use crate::model::{
+    defaults, is_valid_lifetime, validate_custom_fields, GrantType, Issuer, KidFormat, PrivateJwk, Subject,
+    TokenDialect, Trigger, Webhook, MAX_TOKEN_LIFETIME,
+};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -27,9 +35,30 @@ pub struct Config {
     #[serde(default)]
     user: Vec<UserConfig>,
 
+    #[serde(default)]
+    role: Vec<RoleConfig>,
+
+    #[serde(default)]
+    action: Vec<ActionConfig>,
+
+    #[serde(default)]
+    client: Vec<ClientConfig>,
+
     #[serde(default)]
     access_token: AccessTokenConfig,
 
+    #[serde(default)]
+    id_token: IdTokenConfig,
+
+    #[serde(default)]
+    jwks: JwksConfig,
+
+    #[serde(default)]
+    authorization_code: AuthorizationCodeConfig,
+
+    #[serde(default)]
+    refresh_token: RefreshTokenConfig,
+
     #[serde(default)]
     http: Http,
 
@@ -45,7 +74,14 @@ impl Default for Config {
             user_info: Default::default(),
             audience: vec![],
             user: vec![],
+            role: vec![],
+            action: vec![],
+            client: vec![],
             access_token: Default::default(),
+            id_token: Default::default(),
+            jwks: Default::default(),
+            authorization_code: Default::default(),
+            refresh_token: Default::default(),
             http: Default::default(),
             https: Default::default(),
         }
@@ -62,6 +98,9 @@ pub enum Error {
 
     #[error(transparent)]
     TomlError(#[from] toml::de::Error),
+
+    #[error("{0}")]
+    InvalidConfig(String),
 }
 
 impl Config {
@@ -77,6 +116,9 @@ impl Config {
                 Error::Utf8Error(error) => {
                     tracing::error!("Config file is not utf8 encoded: {}", error);
                 }
+                Error::InvalidConfig(error) => {
+                    tracing::error!("Invalid config: {}", error);
+                }
             })
             .unwrap_or_default()
     }
@@ -102,22 +144,215 @@ impl Config {
             None => Ok(Default::default()),
             Some(cfg) => {
                 let cfg_str = std::str::from_utf8(cfg)?;
-                Ok(toml::from_str(cfg_str)?)
+                let config: Self = toml::from_str(cfg_str)?;
+                config.validate()?;
+                Ok(config)
+            }
+        }
+    }
+
+    /// Checks the settings serde can't check on its own
+    fn validate(&self) -> Result<()> {
+        // Templates set through the API are checked by its endpoints
+        let mut custom_fields: Vec<(String, &[CustomField])> = vec![
+            (
+                "[access_token] custom_claims".to_string(),
+                &self.access_token.custom_claims,
+            ),
+            (
+                "[user_info] custom_fields".to_string(),
+                self.user_info.custom_fields.as_deref().unwrap_or_default(),
+            ),
+        ];
+        for user in &self.user {
+            custom_fields.push((
+                format!("[[user]] '{}' custom_fields", user.name),
+                user.custom_fields.as_deref().unwrap_or_default(),
+            ));
+        }
+        for (name, custom_fields) in custom_fields {
+            validate_custom_fields(custom_fields).map_err(|error| Error::InvalidConfig(format!("{name}: {error}")))?;
+        }
+
+        let mut token_lifetimes: Vec<(String, i64)> = vec![
+            ("[access_token] lifetime".to_string(), self.access_token.lifetime),
+            ("[id_token] lifetime".to_string(), self.id_token.lifetime),
+        ];
+        for audience in &self.audience {
+            if let Some(lifetime) = audience.token_lifetime {
+                token_lifetimes.push((format!("[[audience]] '{}' token_lifetime", audience.name), lifetime));
             }
         }
+        for client in &self.client {
+            if let Some(lifetime) = client.id_token_lifetime {
+                token_lifetimes.push((format!("[[client]] '{}' id_token_lifetime", client.id), lifetime));
+            }
+        }
+        if let Some((name, lifetime)) = token_lifetimes
+            .into_iter()
+            .find(|(_, lifetime)| !is_valid_lifetime(*lifetime))
+        {
+            return Err(Error::InvalidConfig(format!(
+                "{name} ({lifetime}s) must be a positive number of seconds, up to {MAX_TOKEN_LIFETIME}"
+            )));
+        }
+
+        if let (Some(interval), Some(overlap)) = (self.jwks.rotation_interval, self.jwks.rotation_overlap) {
+            if overlap > interval.get() {
+                return Err(Error::InvalidConfig(format!(
+                    "jwks rotation_overlap ({overlap}s) can't be longer than the rotation_interval ({interval}s)"
+                )));
+            }
+        }
+
+        check_duration("[authorization_code] ttl", self.authorization_code.ttl, 1)?;
+        check_duration(
+            "[refresh_token] absolute_lifetime",
+            self.refresh_token.absolute_lifetime,
+            1,
+        )?;
+        check_duration(
+            "[refresh_token] inactivity_lifetime",
+            self.refresh_token.inactivity_lifetime,
+            1,
+        )?;
+        check_duration("[refresh_token] reuse_interval", self.refresh_token.reuse_interval, 0)?;
+
+        Ok(())
+    }
+}
+
+/// Durations in seconds must be at least `min` and fit a `TimeDelta`
+fn check_duration(name: &str, seconds: i64, min: i64) -> Result<()> {
+    if seconds < min || TimeDelta::try_seconds(seconds).is_none() {
+        return Err(Error::InvalidConfig(format!(
+            "{name} ({seconds}s) must be a number of seconds of at least {min}"
+        )));
     }
+
+    Ok(())
 }
 
 #[derive(Debug, Deserialize, Getters)]
 pub struct AudienceConfig {
+    /// Identifier of the API, that is the `aud` claim of its access tokens
     name: String,
+    /// Display name, used by the management API
+    display_name: Option<String>,
+    /// Scopes declared by the API
+    #[serde(default)]
     permissions: Vec<String>,
+    /// Lifetime in seconds of the access tokens issued for the API
+    token_lifetime: Option<i64>,
+    #[serde(default = "defaults::audience_signing_alg")]
+    signing_alg: Algorithm,
+    /// Shared secret of the HS algorithms. Generated when missing.
+    signing_secret: Option<String>,
+    /// When disabled users are granted every permission of the API, whatever their permissions and roles
+    #[serde(default = "defaults::audience_enable_rbac")]
+    enable_rbac: bool,
+    #[serde(default = "defaults::audience_add_permissions_in_token")]
+    add_permissions_in_token: bool,
+    #[serde(default)]
+    token_dialect: TokenDialect,
+    /// Service called before signing the access tokens issued for the API
+    webhook: Option<Webhook>,
 }
 
-#[derive(Debug, Deserialize, Getters)]
+#[derive(Debug, Deserialize, Getters, Clone)]
 pub struct UserConfig {
+    /// `sub` claim of the tokens issued to the user. Defaults to the user name.
+    subject: Option<String>,
     name: String,
-    permissions: Vec<String>,
+    /// Permissions granted to the user. Missing means every permission of the requested audience.
+    permissions: Option<Vec<String>>,
+    /// Names of the roles assigned to the user
+    #[serde(default)]
+    roles: Vec<String>,
+    /// Exposed by the management API, see <https://auth0.com/docs/manage-users/user-accounts/metadata>
+    #[serde(default)]
+    app_metadata: Map<String, Value>,
+    #[serde(default)]
+    user_metadata: Map<String, Value>,
+    #[serde(default = "defaults::user_info_given_name")]
+    given_name: String,
+    #[serde(default = "defaults::user_info_family_name")]
+    family_name: String,
+    #[serde(default = "defaults::user_info_nickname")]
+    nickname: String,
+    #[serde(default = "defaults::user_info_locale")]
+    locale: String,
+    #[serde(default = "defaults::user_info_gender")]
+    gender: String,
+    #[serde(default = "defaults::user_info_birthdate")]
+    birthdate: String,
+    #[serde(default = "defaults::user_info_email")]
+    email: String,
+    #[serde(default = "defaults::user_info_email_verified")]
+    email_verified: bool,
+    #[serde(default = "defaults::user_info_picture")]
+    picture: String,
+    #[serde(default = "defaults::user_info_updated_at")]
+    updated_at: DateTime<Utc>,
+    custom_fields: Option<Vec<CustomField>>,
+}
+
+#[derive(Debug, Deserialize, Getters, Clone)]
+pub struct RoleConfig {
+    name: String,
+    description: Option<String>,
+    /// Permissions granted by the role, keyed by audience
+    #[serde(default)]
+    permissions: HashMap<String, Vec<String>>,
+}
+
+/// Script run while issuing tokens, see <https://auth0.com/docs/customize/actions>
+#[derive(Debug, Deserialize, Getters, Clone)]
+pub struct ActionConfig {
+    name: String,
+    trigger: Trigger,
+    /// Rhai script, run with the `event` and `api` variables in scope
+    script: String,
+}
+
+#[derive(Debug, Deserialize, Getters, Clone)]
+pub struct ClientConfig {
+    id: String,
+    secret: String,
+    name: Option<String>,
+    /// Public clients, like single page applications, may omit their secret on the authorization code and refresh
+    /// token grants
+    #[serde(default)]
+    public: bool,
+    /// Grant types the client is allowed to use. Empty means every supported grant type.
+    #[serde(default)]
+    grant_types: Vec<GrantType>,
+    /// Redirect uris the client is allowed to use. Empty means any redirect uri.
+    #[serde(default)]
+    callback_urls: Vec<String>,
+    /// Audiences the client is allowed to request tokens for. Empty means any audience.
+    #[serde(default)]
+    audiences: Vec<String>,
+    /// Lifetime in seconds of the id tokens issued to the client. Missing means the `[id_token]` one.
+    id_token_lifetime: Option<i64>,
+    /// Service called before signing the tokens issued to the client
+    webhook: Option<Webhook>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            id: defaults::client_id(),
+            secret: defaults::client_secret(),
+            name: Some(defaults::client_name()),
+            public: true,
+            grant_types: vec![],
+            callback_urls: vec![],
+            audiences: vec![],
+            id_token_lifetime: None,
+            webhook: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Getters, Clone)]
@@ -169,10 +404,127 @@ impl Default for UserInfoConfig {
     }
 }
 
-#[derive(Debug, Deserialize, Getters, Default)]
+#[derive(Debug, Deserialize, Getters)]
 pub struct AccessTokenConfig {
     #[serde(default)]
     custom_claims: Vec<CustomField>,
+    /// Name of the claim listing the roles of the user. When missing roles are not added to the tokens.
+    roles_claim: Option<String>,
+    /// Lifetime in seconds of the access tokens, unless the audience defines its own
+    #[serde(default = "defaults::access_token_lifetime")]
+    lifetime: i64,
+}
+
+impl Default for AccessTokenConfig {
+    fn default() -> Self {
+        Self {
+            custom_claims: vec![],
+            roles_claim: None,
+            lifetime: defaults::access_token_lifetime(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Getters)]
+pub struct IdTokenConfig {
+    /// Lifetime in seconds of the id tokens, unless the client defines its own
+    #[serde(default = "defaults::id_token_lifetime")]
+    lifetime: i64,
+}
+
+impl Default for IdTokenConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: defaults::id_token_lifetime(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Getters)]
+pub struct JwksConfig {
+    /// Algorithms keys are generated for, besides the ones of the audiences. The first one signs the id tokens.
+    #[serde(default = "defaults::jwks_signing_algs")]
+    signing_algs: Vec<Algorithm>,
+    /// Directory the keys are persisted to, so that restarts keep the same keys. Keys found there are loaded on start.
+    directory: Option<PathBuf>,
+    /// Keys to sign with, loaded on start. Keys are only generated for the algorithms none of them signs with.
+    #[serde(default)]
+    key: Vec<KeyConfig>,
+    /// `kid` of the generated keys, either a random UUID or the JWK thumbprint
+    #[serde(default)]
+    kid_format: KidFormat,
+    /// Seconds between two rotations of the keys, disabled unless set
+    rotation_interval: Option<NonZeroU64>,
+    /// Seconds the previous keys stay published after a rotation, at most the `rotation_interval`. Unless set they
+    /// stay until the following rotation.
+    rotation_overlap: Option<u64>,
+}
+
+impl Default for JwksConfig {
+    fn default() -> Self {
+        Self {
+            signing_algs: defaults::jwks_signing_algs(),
+            directory: None,
+            key: vec![],
+            kid_format: KidFormat::default(),
+            rotation_interval: None,
+            rotation_overlap: None,
+        }
+    }
+}
+
+/// Signing key given either as a PEM or JWK file, as an inline PEM or as an inline JWK
+#[derive(Debug, Deserialize, Getters)]
+pub struct KeyConfig {
+    /// Defaults to the `kid` of the JWK or to the file name without extension
+    kid: Option<String>,
+    /// Defaults to the `alg` of the JWK or to the algorithm matching the key type
+    alg: Option<Algorithm>,
+    /// Files with the `.json` extension hold a JWK, the other ones a PEM
+    path: Option<PathBuf>,
+    pem: Option<String>,
+    jwk: Option<PrivateJwk>,
+}
+
+#[derive(Debug, Deserialize, Getters)]
+pub struct AuthorizationCodeConfig {
+    /// Seconds after which an unused authorization code expires
+    #[serde(default = "defaults::authorization_code_ttl")]
+    ttl: i64,
+}
+
+impl Default for AuthorizationCodeConfig {
+    fn default() -> Self {
+        Self {
+            ttl: defaults::authorization_code_ttl(),
+        }
+    }
+}
+
+/// Refresh token behaviour. Lifetimes and intervals are expressed in seconds.
+#[derive(Debug, Deserialize, Getters)]
+pub struct RefreshTokenConfig {
+    #[serde(default = "defaults::refresh_token_rotation")]
+    rotation: bool,
+    #[serde(default = "defaults::refresh_token_absolute_lifetime")]
+    absolute_lifetime: i64,
+    #[serde(default = "defaults::refresh_token_inactivity_lifetime")]
+    inactivity_lifetime: i64,
+    /// Time window in which an already rotated refresh token can still be exchanged without being considered a
+    /// reuse.
+    #[serde(default = "defaults::refresh_token_reuse_interval")]
+    reuse_interval: i64,
+}
+
+impl Default for RefreshTokenConfig {
+    fn default() -> Self {
+        Self {
+            rotation: defaults::refresh_token_rotation(),
+            absolute_lifetime: defaults::refresh_token_absolute_lifetime(),
+            inactivity_lifetime: defaults::refresh_token_inactivity_lifetime(),
+            reuse_interval: defaults::refresh_token_reuse_interval(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Getters, Clone)]
@@ -180,11 +532,42 @@ pub struct AccessTokenConfig {
 pub struct CustomField {
     name: String,
     value: CustomFieldValue,
+    /// Audiences the field is added for. Empty means every audience.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    audiences: Vec<String>,
+    /// Ids of the clients the field is added for. Empty means every client.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    clients: Vec<String>,
+    /// Grant types the field is added for. Empty means every grant type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    grant_types: Vec<GrantType>,
+    /// Subjects of the users the field is added for. Empty means every user.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    users: Vec<String>,
 }
 
 impl CustomField {
     pub fn new(name: String, value: CustomFieldValue) -> Self {
-        Self { name, value }
+        Self {
+            name,
+            value,
+            audiences: vec![],
+            clients: vec![],
+            grant_types: vec![],
+            users: vec![],
+        }
+    }
+
+    pub fn with_value(self, value: CustomFieldValue) -> Self {
+        Self { value, ..self }
+    }
+
+    /// Whether the field belongs to a token issued for the given audience, client, grant type and subject
+    pub fn applies_to(&self, audience: &str, client_id: &str, grant_type: &GrantType, subject: &str) -> bool {
+        (self.audiences.is_empty() || self.audiences.iter().any(|aud| aud == audience))
+            && (self.clients.is_empty() || self.clients.iter().any(|id| id == client_id))
+            && (self.grant_types.is_empty() || self.grant_types.contains(grant_type))
+            && (self.users.is_empty() || self.users.iter().any(|user| user == subject))
     }
 }
 
@@ -193,6 +576,23 @@ impl CustomField {
 pub enum CustomFieldValue {
     String(String),
     Vec(Vec<String>),
+    Number(serde_json::Number),
+    Bool(bool),
+    /// Any json value, e.g. an object or nested arrays
+    Json(Value),
+}
+
+impl CustomFieldValue {
+    /// Value of the claim as it appears in the tokens
+    pub fn to_json(&self) -> Value {
+        match self {
+            CustomFieldValue::String(string) => Value::String(string.clone()),
+            CustomFieldValue::Vec(vec) => Value::from(vec.clone()),
+            CustomFieldValue::Number(number) => Value::Number(number.clone()),
+            CustomFieldValue::Bool(bool) => Value::Bool(*bool),
+            CustomFieldValue::Json(value) => value.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Getters)]
@@ -225,8 +625,8 @@ impl Default for Https {
 mod tests {
     use chrono::DateTime;
 
-    use crate::config::{AudienceConfig, Config, CustomField, CustomFieldValue};
-    use crate::model::Issuer;
+    use crate::config::{AudienceConfig, ClientConfig, Config, CustomField, CustomFieldValue, UserConfig};
+    use crate::model::{GrantType, Issuer};
 
     #[test]
     fn local_localauth0_config_file_is_loadable() {
@@ -266,6 +666,23 @@ mod tests {
         name = "audience2"
         permissions = ["audience2:permission2"]
 
+        [[client]]
+        id = "client1"
+        secret = "secret1"
+        name = "Client 1"
+        grant_types = ["client_credentials"]
+        callback_urls = ["http://localhost:8080/callback"]
+        audiences = ["audience1"]
+
+        [[user]]
+        subject = "auth0|admin"
+        name = "Admin"
+        email = "admin@example.com"
+        permissions = ["audience1:permission1"]
+
+        [[user]]
+        name = "guest"
+
         [access_token]
         custom_claims = [
             { name = "at_custom_claim_str", value = { String = "str" } }
@@ -308,6 +725,25 @@ mod tests {
         assert!(audience2.is_some());
         assert_eq!(audience2.unwrap().permissions, ["audience2:permission2"]);
 
+        assert_eq!(config.client.len(), 1);
+        let client1: &ClientConfig = &config.client[0];
+        assert_eq!(client1.id, "client1");
+        assert_eq!(client1.secret, "secret1");
+        assert_eq!(client1.name.as_deref(), Some("Client 1"));
+        assert_eq!(client1.grant_types, [GrantType::ClientCredentials]);
+        assert_eq!(client1.callback_urls, ["http://localhost:8080/callback"]);
+        assert_eq!(client1.audiences, ["audience1"]);
+
+        assert_eq!(config.user.len(), 2);
+        let admin: &UserConfig = &config.user[0];
+        assert_eq!(admin.subject.as_deref(), Some("auth0|admin"));
+        assert_eq!(admin.name, "Admin");
+        assert_eq!(admin.email, "admin@example.com");
+        assert_eq!(admin.permissions, Some(vec!["audience1:permission1".to_string()]));
+        let guest: &UserConfig = &config.user[1];
+        assert_eq!(guest.subject, None);
+        assert_eq!(guest.permissions, None);
+
         let custom_fields: &[CustomField] = config.user_info().custom_fields().as_deref().unwrap_or_default();
 
         assert_eq!(custom_fields.len(), 2);
@@ -330,4 +766,122 @@ mod tests {
         assert_eq!(&8000, config.http().port());
         assert_eq!(&3001, config.https().port());
     }
+
+    #[test]
+    fn typed_custom_claims_are_loadable() {
+        let config_str: &str = r#"
+        [access_token]
+        custom_claims = [
+            { name = "count", value = { Number = 42 } },
+            { name = "ratio", value = { Number = 0.5 } },
+            { name = "admin", value = { Bool = true } },
+            { name = "https://example.com/tenant", value = { Json = { id = "acme", plan = { seats = 3 } } } },
+        ]
+        "#;
+
+        let config: Config = toml::from_str(config_str).unwrap();
+        let values: Vec<serde_json::Value> = config
+            .access_token()
+            .custom_claims()
+            .iter()
+            .map(|claim| claim.value().to_json())
+            .collect();
+
+        assert_eq!(
+            values,
+            vec![
+                serde_json::json!(42),
+                serde_json::json!(0.5),
+                serde_json::json!(true),
+                serde_json::json!({ "id": "acme", "plan": { "seats": 3 } }),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_key_rotation_is_rejected() {
+        assert!(toml::from_str::<Config>("[jwks]\nrotation_interval = 0").is_err());
+
+        let config: Config = toml::from_str("[jwks]\nrotation_interval = 60\nrotation_overlap = 60").unwrap();
+        assert!(config.validate().is_ok());
+
+        let config: Config = toml::from_str("[jwks]\nrotation_interval = 60\nrotation_overlap = 61").unwrap();
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn invalid_token_lifetimes_are_rejected() {
+        for config_str in [
+            "[access_token]\nlifetime = 0",
+            "[id_token]\nlifetime = -1",
+            "[[audience]]\nname = \"api\"\ntoken_lifetime = 9223372036854775807",
+            "[[client]]\nid = \"client\"\nsecret = \"secret\"\nid_token_lifetime = 0",
+        ] {
+            let config: Config = toml::from_str(config_str).unwrap();
+            assert!(config.validate().is_err(), "{config_str}");
+        }
+
+        let config: Config = toml::from_str("[access_token]\nlifetime = 60").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_refresh_token_durations_are_rejected() {
+        for config_str in [
+            "[refresh_token]\nabsolute_lifetime = 0",
+            "[refresh_token]\nabsolute_lifetime = 9223372036854775807",
+            "[refresh_token]\ninactivity_lifetime = -1",
+            "[refresh_token]\nreuse_interval = -1",
+        ] {
+            let config: Config = toml::from_str(config_str).unwrap();
+            assert!(config.validate().is_err(), "{config_str}");
+        }
+
+        let config: Config = toml::from_str("[refresh_token]\nreuse_interval = 0").unwrap();
+        assert!(config.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid_authorization_code_ttl_is_rejected() {
+        for config_str in [
+            "[authorization_code]\nttl = 0",
+            "[authorization_code]\nttl = -1",
+            "[authorization_code]\nttl = 9223372036854775807",
+        ] {
+            let config: Config = toml::from_str(config_str).unwrap();
+            assert!(config.validate().is_err(), "{config_str}");
+        }
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        for config_str in [
+            r#"
+            [access_token]
+            custom_claims = [{ name = "tenant", value = { String = "{{ user.name" } }]
+            "#,
+            r#"
+            [user_info]
+            custom_fields = [{ name = "tenant", value = { String = "{{ user.name" } }]
+            "#,
+            r#"
+            [[user]]
+            name = "alice"
+            custom_fields = [{ name = "tenant", value = { String = "{{ user.name" } }]
+            "#,
+        ] {
+            let config: Config = toml::from_str(config_str).unwrap();
+            let error: String = config.validate().err().unwrap().to_string();
+            assert!(error.contains("Invalid template"), "{error}");
+        }
+
+        let config: Config = toml::from_str(
+            r#"
+            [access_token]
+            custom_claims = [{ name = "tenant", value = { String = "{{ user.name }}" } }]
+            "#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+    }
 }
@@ -1,16 +1,24 @@
 use std::collections::HashMap;
 
-use actix_web::web::{Data, Either, Form, Json, Path};
-use actix_web::{get, post, HttpRequest, HttpResponse};
-
+use actix_web::http::header;
+use actix_web::web::{Bytes, Data, Json, Path};
+use actix_web::{delete, get, post, route, HttpMessage, HttpRequest, HttpResponse};
+use chrono::Utc;
+
+use crate::config::{CustomField, CustomFieldValue};
+use crate::error::Error;
+use crate::management;
 use crate::model::{
-    AppData, AuthorizationCodeTokenRequest, Claims, ClientCredentialsTokenRequest, GrantType, IdTokenClaims, Jwk, Jwks,
-    LoginRequest, LoginResponse, OpenIDMetadata, PermissionsForAudienceRequest, TokenRequest, TokenResponse,
-    UpdateCustomClaimsRequest, UpdateUserInfoRequest,
+    encode_with_secret, is_symmetric_alg, is_valid_lifetime, render_custom_fields, validate_custom_fields, Action,
+    AppData, Audience, Authorization, AuthorizationCodeTokenRequest, Claims, ClaimsPatch, Client,
+    ClientCredentialsTokenRequest, CodeChallenge, GrantType, IdTokenClaims, Jwks, LoginRequest, LoginResponse,
+    OpenIDMetadata, PermissionsForAudienceRequest, RefreshToken, RefreshTokenRequest, Role, TokenRequest,
+    TokenResponse, UpdateCustomClaimsRequest, UpdateUserInfoRequest, User, UserInfo, UserRolesRequest,
+    MAX_TOKEN_LIFETIME, OFFLINE_ACCESS_SCOPE, OPENID_SCOPES,
 };
-use crate::{CLIENT_ID_VALUE, CLIENT_SECRET_VALUE};
+use crate::BEARER;
 
-/// Remove one jwk and generate new one
+/// Liveness probe
 #[get("/check")]
 pub async fn healthcheck() -> HttpResponse {
     HttpResponse::Ok().content_type("text/plain").body("ok")
@@ -18,11 +26,11 @@ pub async fn healthcheck() -> HttpResponse {
 
 /// .well-known/jwks.json route. This is the standard route exposed by authorities to fetch jwks
 #[get("/.well-known/jwks.json")]
-pub async fn jwks(app_data: Data<AppData>) -> HttpResponse {
-    let jwks: Jwks = app_data.jwks().get().expect("Failed to read JWKS");
-    HttpResponse::Ok()
+pub async fn jwks(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    let jwks: Jwks = app_data.jwks().get()?;
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(serde_json::to_string(&jwks).expect("Failed to serialize JWKS to json"))
+        .body(serde_json::to_string(&jwks)?))
 }
 
 impl jwks {
@@ -31,14 +39,13 @@ impl jwks {
 
 /// Generate a new jwt token for a given audience. For `client_credentials` the audience is found in the post body
 /// and for `authorization_code` the audience is found in the authorizations cache.
-/// All the permissions found in the local store will be included in the generated token.
+/// The permissions found in the local store are included in the generated token, restricted to the requested scope if any.
 #[post("/oauth/token")]
-async fn token(app_data: Data<AppData>, token_request: Either<Json<TokenRequest>, Form<TokenRequest>>) -> HttpResponse {
-    let (Either::Left(Json(token_request)) | Either::Right(Form(token_request))) = token_request;
-
-    match token_request {
+async fn token(app_data: Data<AppData>, req: HttpRequest, body: Bytes) -> Result<HttpResponse, Error> {
+    match TokenRequest::from_body(req.content_type(), &body)? {
         TokenRequest::ClientCredentials(request) => jwt_for_client_credentials(app_data, request).await,
         TokenRequest::AuthorizationCode(request) => jwt_for_authorization_code(app_data, request).await,
+        TokenRequest::RefreshToken(request) => jwt_for_refresh_token(app_data, request).await,
     }
 }
 
@@ -48,30 +55,180 @@ impl token {
 
 /// Logs the "user" in and returns an auth code which can be exchanged for a token
 #[post("/oauth/login")]
-pub async fn login(app_data: Data<AppData>, login_request: Json<LoginRequest>) -> HttpResponse {
+pub async fn login(app_data: Data<AppData>, login_request: Json<LoginRequest>) -> Result<HttpResponse, Error> {
+    let LoginRequest {
+        audience,
+        client_id,
+        redirect_uri,
+        scope,
+        nonce,
+        state: _,
+        code_challenge,
+        code_challenge_method,
+        user,
+    } = login_request.into_inner();
+
+    if let Some(client_id) = &client_id {
+        let client: Client = app_data
+            .clients()
+            .get(client_id)?
+            .ok_or_else(|| Error::InvalidClient(format!("Unknown client '{client_id}'")))?;
+
+        if redirect_uri
+            .as_deref()
+            .is_some_and(|uri| !client.allows_callback_url(uri))
+        {
+            return Err(callback_url_mismatch());
+        }
+    }
+
+    if let Some(user) = &user {
+        match app_data.users().get(user)? {
+            None => return Err(Error::InvalidRequest(format!("Unknown user '{user}'"))),
+            Some(user) if user.blocked() => return Err(Error::AccessDenied("user is blocked".to_string())),
+            Some(_) => (),
+        }
+    }
+
     let code = uuid::Uuid::new_v4().to_string();
-    app_data
-        .authorizations()
-        .put_authorization(&code, login_request.0.audience)
-        .expect("Failed to insert authorization");
+    let authorization: Authorization = Authorization {
+        audience,
+        client_id,
+        redirect_uri,
+        scope,
+        nonce,
+        code_challenge: code_challenge
+            .map(|challenge| CodeChallenge::new(challenge, code_challenge_method.unwrap_or_default())),
+        subject: user,
+        created_at: Utc::now(),
+    };
 
-    HttpResponse::Ok()
+    app_data.authorizations().put_authorization(&code, authorization)?;
+
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(serde_json::to_string(&LoginResponse { code }).expect("Failed to serialize login response to json"))
+        .body(serde_json::to_string(&LoginResponse { code })?))
 }
 impl login {
     pub const ENDPOINT: &'static str = "/oauth/login";
 }
 
+/// List the users that can be picked while logging in
+#[get("/users")]
+pub async fn get_users(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(app_data.users().all()?))
+}
+
+/// List the roles assigned to the given user
+#[get("/users/{subject}/roles")]
+pub async fn get_user_roles(app_data: Data<AppData>, subject: Path<String>) -> Result<HttpResponse, Error> {
+    let user: User = app_data
+        .users()
+        .get(&subject)?
+        .ok_or_else(|| user_not_found(&subject))?;
+
+    Ok(HttpResponse::Ok().json(app_data.roles().get_all(user.roles())?))
+}
+
+/// Assign the given roles to the user
+#[post("/users/{subject}/roles")]
+pub async fn assign_user_roles(
+    app_data: Data<AppData>,
+    subject: Path<String>,
+    user_roles_request: Json<UserRolesRequest>,
+) -> Result<HttpResponse, Error> {
+    let roles: Vec<String> = user_roles_request.into_inner().roles;
+    if let Some(role) = app_data.roles().find_unknown(&roles)? {
+        return Err(Error::InvalidRequest(format!("Unknown role '{role}'")));
+    }
+
+    let user: User = app_data
+        .users()
+        .assign_roles(&subject, roles)?
+        .ok_or_else(|| user_not_found(&subject))?;
+
+    Ok(HttpResponse::Ok().json(app_data.roles().get_all(user.roles())?))
+}
+
+/// Remove the given roles from the user
+#[delete("/users/{subject}/roles")]
+pub async fn remove_user_roles(
+    app_data: Data<AppData>,
+    subject: Path<String>,
+    user_roles_request: Json<UserRolesRequest>,
+) -> Result<HttpResponse, Error> {
+    let user: User = app_data
+        .users()
+        .remove_roles(&subject, &user_roles_request.into_inner().roles)?
+        .ok_or_else(|| user_not_found(&subject))?;
+
+    Ok(HttpResponse::Ok().json(app_data.roles().get_all(user.roles())?))
+}
+
+/// List all the roles
+#[get("/roles")]
+pub async fn get_roles(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(app_data.roles().all()?))
+}
+
+/// Create a role, or replace the one with the same name
+#[post("/roles")]
+pub async fn set_role(app_data: Data<AppData>, role: Json<Role>) -> Result<HttpResponse, Error> {
+    let role: Role = role.into_inner();
+    app_data.roles().put(role.clone())?;
+
+    Ok(HttpResponse::Ok().json(role))
+}
+
+#[get("/roles/{name}")]
+pub async fn get_role(app_data: Data<AppData>, name: Path<String>) -> Result<HttpResponse, Error> {
+    let role: Role = app_data.roles().get(&name)?.ok_or_else(|| role_not_found(&name))?;
+
+    Ok(HttpResponse::Ok().json(role))
+}
+
+/// Delete the role, unassigning it from every user
+#[delete("/roles/{name}")]
+pub async fn delete_role(app_data: Data<AppData>, name: Path<String>) -> Result<HttpResponse, Error> {
+    app_data.roles().remove(&name)?.ok_or_else(|| role_not_found(&name))?;
+    app_data.users().unassign_role(&name)?;
+
+    Ok(HttpResponse::Ok().into())
+}
+
+/// List all the actions, in the order they are run
+#[get("/actions")]
+pub async fn get_actions(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(app_data.actions().all()?))
+}
+
+/// Create an action, or replace the one with the same name
+#[post("/actions")]
+pub async fn set_action(app_data: Data<AppData>, action: Json<Action>) -> Result<HttpResponse, Error> {
+    let action: Action = action.into_inner();
+    app_data.actions().put(action.clone())?;
+
+    Ok(HttpResponse::Ok().json(action))
+}
+
+#[delete("/actions/{name}")]
+pub async fn delete_action(app_data: Data<AppData>, name: Path<String>) -> Result<HttpResponse, Error> {
+    app_data
+        .actions()
+        .remove(&name)?
+        .ok_or_else(|| Error::NotFound(format!("Action '{name}' not found")))?;
+
+    Ok(HttpResponse::Ok().into())
+}
+
 /// List all audience-permissions mappings present in local implementation
 #[get("/permissions")]
-pub async fn get_permissions(app_data: Data<AppData>) -> HttpResponse {
-    let all_audiences: HashMap<String, Vec<String>> =
-        app_data.audiences().all().expect("Failed to get inner audiences cache");
+pub async fn get_permissions(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    let all_audiences: HashMap<String, Vec<String>> = app_data.audiences().all()?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(serde_json::to_string(&all_audiences).expect("Failed to serialize permissions list"))
+        .body(serde_json::to_string(&all_audiences)?))
 }
 
 /// Set the given list of permissions for a given audience
@@ -79,193 +236,622 @@ pub async fn get_permissions(app_data: Data<AppData>) -> HttpResponse {
 pub async fn set_permissions_for_audience(
     app_data: Data<AppData>,
     permissions_for_audience_request: Json<PermissionsForAudienceRequest>,
-) -> HttpResponse {
-    app_data
-        .audiences()
-        .put_permissions(
-            &permissions_for_audience_request.0.audience,
-            permissions_for_audience_request.0.permissions,
-        )
-        .expect("Failed to put permissions for given audience");
+) -> Result<HttpResponse, Error> {
+    app_data.audiences().put_permissions(
+        &permissions_for_audience_request.0.audience,
+        permissions_for_audience_request.0.permissions,
+        app_data.jwks().default_signing_alg(),
+    )?;
 
-    let all_audiences: HashMap<String, Vec<String>> =
-        app_data.audiences().all().expect("Failed to get inner audiences cache");
+    let all_audiences: HashMap<String, Vec<String>> = app_data.audiences().all()?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(serde_json::to_string(&all_audiences).expect("Failed to serialize audiences map"))
+        .body(serde_json::to_string(&all_audiences)?))
 }
 
 /// List all permissions set for a given audience
 #[get("/permissions/{audience}")]
-pub async fn get_permissions_by_audience(app_data: Data<AppData>, audience: Path<String>) -> HttpResponse {
+pub async fn get_permissions_by_audience(
+    app_data: Data<AppData>,
+    audience: Path<String>,
+) -> Result<HttpResponse, Error> {
     let audience: String = audience.into_inner();
-    let permissions: Vec<String> = app_data
-        .audiences()
-        .get_permissions(audience.as_str())
-        .expect("Failed to get permissions by given audience");
+    let permissions: Vec<String> = app_data.audiences().get_permissions(audience.as_str())?;
 
-    HttpResponse::Ok()
+    Ok(HttpResponse::Ok()
         .content_type("application/json")
-        .body(serde_json::to_string(&permissions).expect("Failed to serialize permissions list"))
+        .body(serde_json::to_string(&permissions)?))
+}
+
+/// Shared secret signing the access tokens of an audience configured for an HS algorithm
+#[get("/audiences/{audience}/signing_secret")]
+pub async fn get_signing_secret(app_data: Data<AppData>, audience: Path<String>) -> Result<HttpResponse, Error> {
+    let signing_secret: String = app_data
+        .audiences()
+        .get(&audience)?
+        .and_then(|audience| audience.signing_secret().map(str::to_string))
+        .ok_or_else(|| Error::NotFound(format!("Audience '{audience}' has no signing secret")))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "signing_secret": signing_secret })))
 }
 
 #[get("/oauth/token/custom_claims")]
-pub async fn get_custom_claims(app_data: Data<AppData>) -> HttpResponse {
-    let custom_claims = app_data.custom_claims().all().expect("Failed to get custom claims");
-    HttpResponse::Ok().json(custom_claims)
+pub async fn get_custom_claims(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    let custom_claims = app_data.custom_claims().all()?;
+    Ok(HttpResponse::Ok().json(custom_claims))
 }
 
 #[post("/oauth/token/custom_claims")]
 pub async fn set_custom_claims(
     app_data: Data<AppData>,
     update_custom_claims_request: Json<UpdateCustomClaimsRequest>,
-) -> HttpResponse {
+) -> Result<HttpResponse, Error> {
+    validate_custom_fields(&update_custom_claims_request.custom_claims)?;
     app_data
         .custom_claims()
-        .put_custom_fields(update_custom_claims_request.into_inner().custom_claims)
-        .expect("Failed to update custom_claims");
+        .put_custom_fields(update_custom_claims_request.into_inner().custom_claims)?;
 
-    HttpResponse::Ok().into()
+    Ok(HttpResponse::Ok().into())
 }
 
 #[get("/oauth/token/user_info")]
-pub async fn get_user_info(app_data: Data<AppData>) -> HttpResponse {
-    let user_info = app_data.user_info().get().expect("Failed to get user info");
-    HttpResponse::Ok().json(user_info)
+pub async fn get_user_info(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    let user_info = app_data.user_info().get()?;
+    Ok(HttpResponse::Ok().json(user_info))
 }
 
 #[post("/oauth/token/user_info")]
 pub async fn set_user_info(
     app_data: Data<AppData>,
     update_user_info_request: Json<UpdateUserInfoRequest>,
-) -> HttpResponse {
-    let user_info = app_data
-        .user_info()
-        .update(update_user_info_request.into_inner())
-        .expect("Failed to update user info");
+) -> Result<HttpResponse, Error> {
+    if let Some(custom_fields) = &update_user_info_request.custom_fields {
+        validate_custom_fields(custom_fields)?;
+    }
+
+    let user_info = app_data.user_info().update(update_user_info_request.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(user_info))
+}
+
+/// Standard OpenID Connect userinfo endpoint. Returns the profile of the user the given bearer token has been
+/// issued to, filtered by the scopes granted to the token.
+/// See <https://openid.net/specs/openid-connect-core-1_0.html#UserInfo>
+#[route("/userinfo", method = "GET", method = "POST")]
+pub async fn userinfo(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    let access_token: &str = bearer_token(&req)?;
+
+    // The audience tells how the token has been signed: with its secret or with the published keys
+    let audience: String = jsonwebtoken::dangerous::insecure_decode::<Claims>(access_token)
+        .map_err(|error| Error::InvalidToken(error.to_string()))?
+        .claims
+        .audience()
+        .to_string();
+    let claims: Claims = app_data.verify_access_token(access_token, &audience)?;
+
+    let user: Option<User> = app_data.users().get(claims.subject())?;
+    let mut user_info: UserInfo = match &user {
+        Some(user) => user.user_info().clone(),
+        None => app_data.user_info().get()?,
+    };
+
+    if user_info.sub != claims.subject() {
+        return Err(Error::InvalidToken(format!("Unknown user '{}'", claims.subject())));
+    }
+
+    if let Some(custom_fields) = user_info.custom_fields.take() {
+        let custom_fields: Vec<CustomField> = custom_fields
+            .into_iter()
+            .filter(|custom_field| {
+                custom_field.applies_to(
+                    claims.audience(),
+                    claims.authorized_party(),
+                    claims.grant_type(),
+                    claims.subject(),
+                )
+            })
+            .collect();
+
+        let context: serde_json::Value = template_context(
+            match &user {
+                Some(user) => serde_json::to_value(user)?,
+                None => serde_json::to_value(&user_info)?,
+            },
+            app_data.clients().get(claims.authorized_party())?.as_ref(),
+            claims.audience(),
+            claims.grant_type(),
+            claims.scope(),
+        );
+        user_info.custom_fields = Some(render_custom_fields(&custom_fields, &context)?);
+    }
+
+    Ok(HttpResponse::Ok().json(user_info.claims_for_scope(claims.scope())?))
+}
 
-    HttpResponse::Ok().json(user_info)
+impl userinfo {
+    pub const ENDPOINT: &'static str = "/userinfo";
 }
 
-/// Remove one jwk and generate new one
+/// Rotate the keys of every signing algorithm: the next key becomes the current one, the current key becomes the
+/// previous one and a new next key is generated. The former previous keys stop being published.
 #[get("/rotate")]
-pub async fn rotate_keys(app_data: Data<AppData>) -> HttpResponse {
-    app_data.jwks().rotate_keys().expect("Failed to rotate keys");
-    HttpResponse::Ok().content_type("text/plain").body("ok")
+pub async fn rotate_keys(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    app_data.jwks().rotate_keys()?;
+    Ok(HttpResponse::Ok().content_type("text/plain").body("ok"))
 }
 
-/// Revoke all jwks keys and generate 3 new jwks
+/// Revoke all jwks keys and generate new ones
 #[get("/revoke")]
-pub async fn revoke_keys(app_data: Data<AppData>) -> HttpResponse {
-    app_data.jwks().revoke_keys().expect("Failed to revoke keys");
-    HttpResponse::Ok().content_type("text/plain").body("ok")
+pub async fn revoke_keys(app_data: Data<AppData>) -> Result<HttpResponse, Error> {
+    app_data.jwks().revoke_keys()?;
+    Ok(HttpResponse::Ok().content_type("text/plain").body("ok"))
 }
 
-/// .well-known/jwks.json route. This is the standard route to fetch the openid configuration
+/// Browser authorization page, served by the web frontend
+pub const AUTHORIZE_ENDPOINT: &str = "/authorize";
+
+/// .well-known/openid-configuration route. This is the standard route to fetch the openid configuration
 /// See <https://openid.net/specs/openid-connect-discovery-1_0.html#WellKnownRegistry>
 #[get("/.well-known/openid-configuration")]
-pub async fn openid_configuration(app_data: Data<AppData>, req: HttpRequest) -> HttpResponse {
+pub async fn openid_configuration(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(metadata(&app_data, &req)?))
+}
+
+/// .well-known/oauth-authorization-server route. Serves the same metadata of the openid configuration
+/// See <https://datatracker.ietf.org/doc/html/rfc8414#section-3>
+#[get("/.well-known/oauth-authorization-server")]
+pub async fn oauth_authorization_server(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    Ok(HttpResponse::Ok().json(metadata(&app_data, &req)?))
+}
+
+fn metadata(app_data: &AppData, req: &HttpRequest) -> Result<OpenIDMetadata, Error> {
     let conn = req.connection_info();
     let base_uri = format!("{}://{}", conn.scheme(), conn.host());
-    let jwk = app_data
-        .jwks()
-        .random_jwk()
-        .expect("No JWKs configured. Cannot get openid configuration");
+    let jwk = app_data.jwks().signing_jwk()?;
+    let user_info = app_data.user_info().get()?;
 
-    let metadata = OpenIDMetadata::new(app_data.issuer(), &jwk, &base_uri);
-    HttpResponse::Ok().json(&metadata)
+    let public_clients: bool = app_data.clients().has_public_client()?;
+
+    OpenIDMetadata::new(app_data.issuer(), &jwk, &user_info, public_clients, &base_uri)
 }
 
 pub async fn jwt_for_client_credentials(
     app_data: Data<AppData>,
     request: ClientCredentialsTokenRequest,
-) -> HttpResponse {
-    if request.client_id == CLIENT_ID_VALUE && request.client_secret == CLIENT_SECRET_VALUE {
-        let response: TokenResponse =
-            new_token_response(&app_data, request.audience.as_str(), GrantType::ClientCredentials, None);
-
-        HttpResponse::Ok()
-            .content_type("application/json")
-            .body(serde_json::to_string(&response).expect("Failed to serialize TokenResponse"))
-    } else {
-        HttpResponse::Unauthorized()
-            .content_type("application/json")
-            .body(r#"{"error":"access_denied","error_description":"Unauthorized"}"#)
+) -> Result<HttpResponse, Error> {
+    let client: Client = match app_data.clients().get(&request.client_id)? {
+        Some(client) if client.verify_secret(&request.client_secret) => client,
+        _ => return Err(client_authentication_failed()),
+    };
+
+    if !client.allows_grant_type(&GrantType::ClientCredentials) {
+        return Err(grant_type_not_allowed(&GrantType::ClientCredentials));
+    }
+
+    if !client.allows_audience(&request.audience) {
+        return Err(audience_not_allowed(&request.audience));
     }
+
+    let grant: TokenGrant = TokenGrant::new(&client, request.audience.as_str(), GrantType::ClientCredentials)
+        .with_scope(request.scope.as_deref())
+        .with_lifetime(request.expires_in)?;
+    let response: TokenResponse = new_token_response(&app_data, grant).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(serde_json::to_string(&response)?))
 }
 
 pub async fn jwt_for_authorization_code(
     app_data: Data<AppData>,
     request: AuthorizationCodeTokenRequest,
-) -> HttpResponse {
-    if request.client_id == CLIENT_ID_VALUE {
-        // Client secret is optional for certain clients
-        if let Some(expected_secret) = request.client_secret {
-            if expected_secret != CLIENT_SECRET_VALUE {
-                return HttpResponse::Unauthorized()
-                    .content_type("application/json")
-                    .body(r#"{"error":"access_denied","error_description":"Unauthorized"}"#);
+) -> Result<HttpResponse, Error> {
+    let client: Client = match app_data.clients().get(&request.client_id)? {
+        // Client secret is optional for public clients
+        Some(client) if client.authenticate(request.client_secret.as_deref()) => client,
+        _ => return Err(client_authentication_failed()),
+    };
+
+    if !client.allows_grant_type(&GrantType::AuthorizationCode) {
+        return Err(grant_type_not_allowed(&GrantType::AuthorizationCode));
+    }
+
+    if let Some(redirect_uri) = &request.redirect_uri {
+        if !client.allows_callback_url(redirect_uri) {
+            return Err(callback_url_mismatch());
+        }
+    }
+
+    let authorization: Authorization = app_data.authorizations().take_authorization(&request.code)?;
+
+    if authorization.client_id.as_ref().is_some_and(|id| id != client.id()) {
+        return Err(Error::InvalidGrant(
+            "Authorization code was issued to another client".to_string(),
+        ));
+    }
+
+    if authorization.redirect_uri.is_some() && authorization.redirect_uri != request.redirect_uri {
+        return Err(Error::InvalidGrant("Redirect URI mismatch".to_string()));
+    }
+
+    if authorization.nonce.is_some() && request.nonce.is_some() && authorization.nonce != request.nonce {
+        return Err(Error::InvalidGrant("Nonce mismatch".to_string()));
+    }
+
+    if !client.allows_audience(&authorization.audience) {
+        return Err(audience_not_allowed(&authorization.audience));
+    }
+
+    if let Some(code_challenge) = &authorization.code_challenge {
+        match request.code_verifier.as_deref() {
+            None => return Err(Error::InvalidGrant("Parameter 'code_verifier' is required".to_string())),
+            Some(code_verifier) if !code_challenge.verify(code_verifier) => {
+                return Err(Error::InvalidGrant("Failed to verify code verifier".to_string()))
             }
+            Some(_) => (),
         }
+    }
 
-        let audience: String = app_data
-            .authorizations()
-            .get_audience_for_authorization(&request.code)
-            .expect("Failed to get audience for authorization");
+    let user: Option<User> = logged_in_user(&app_data, authorization.subject.as_deref())?;
 
-        let response: TokenResponse = new_token_response(
-            &app_data,
-            audience.as_str(),
-            GrantType::AuthorizationCode,
-            request.nonce,
-        );
+    let grant: TokenGrant = TokenGrant::new(&client, authorization.audience.as_str(), GrantType::AuthorizationCode)
+        .with_user(user.as_ref())
+        .with_scope(authorization.scope.as_deref())
+        .with_nonce(authorization.nonce.clone().or(request.nonce))
+        .with_lifetime(request.expires_in)?;
+    let mut response: TokenResponse = new_token_response(&app_data, grant).await?;
+
+    if authorization.has_scope(OFFLINE_ACCESS_SCOPE) && client.allows_grant_type(&GrantType::RefreshToken) {
+        let refresh_token: RefreshToken = app_data.refresh_tokens().issue(
+            client.id(),
+            &authorization.audience,
+            authorization.scope.clone(),
+            authorization.subject.clone(),
+        )?;
+
+        response = response.with_refresh_token(Some(refresh_token.token().to_string()));
+    }
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(serde_json::to_string(&response)?))
+}
+
+pub async fn jwt_for_refresh_token(
+    app_data: Data<AppData>,
+    request: RefreshTokenRequest,
+) -> Result<HttpResponse, Error> {
+    let client: Client = match app_data.clients().get(&request.client_id)? {
+        Some(client) if client.authenticate(request.client_secret.as_deref()) => client,
+        _ => return Err(client_authentication_failed()),
+    };
+
+    if !client.allows_grant_type(&GrantType::RefreshToken) {
+        return Err(grant_type_not_allowed(&GrantType::RefreshToken));
+    }
+
+    // The refresh token is only exchanged once the new tokens are issued: a failure leaves it usable
+    let refresh_token: RefreshToken = app_data.refresh_tokens().verify(&request.refresh_token, client.id())?;
+
+    let user: Option<User> = logged_in_user(&app_data, refresh_token.subject())?;
+
+    let grant: TokenGrant = TokenGrant::new(&client, refresh_token.audience(), GrantType::RefreshToken)
+        .with_user(user.as_ref())
+        .with_scope(refresh_token.scope())
+        .with_lifetime(request.expires_in)?;
+    let response: TokenResponse = new_token_response(&app_data, grant).await?;
+
+    let refresh_token: RefreshToken = app_data
+        .refresh_tokens()
+        .exchange(&request.refresh_token, client.id())?;
+
+    // Without rotation auth0 doesn't send the refresh token back, the client keeps using the one it already has
+    let refresh_token: Option<String> = app_data
+        .refresh_tokens()
+        .rotation()
+        .then(|| refresh_token.token().to_string());
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(serde_json::to_string(&response.with_refresh_token(refresh_token))?))
+}
+
+/// Extract the bearer token from the `Authorization` header.
+/// See <https://datatracker.ietf.org/doc/html/rfc6750#section-2.1>
+pub(crate) fn bearer_token(req: &HttpRequest) -> Result<&str, Error> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case(BEARER))
+        .map(|(_, value)| value.trim())
+        .ok_or_else(|| Error::InvalidToken("Missing bearer token".to_string()))
+}
+
+fn user_not_found(subject: &str) -> Error {
+    Error::NotFound(format!("User '{subject}' not found"))
+}
+
+fn role_not_found(name: &str) -> Error {
+    Error::NotFound(format!("Role '{name}' not found"))
+}
+
+fn client_authentication_failed() -> Error {
+    Error::InvalidClient("Client authentication failed".to_string())
+}
+
+fn callback_url_mismatch() -> Error {
+    Error::InvalidRequest("Callback URL mismatch".to_string())
+}
+
+fn grant_type_not_allowed(grant_type: &GrantType) -> Error {
+    Error::UnauthorizedClient(format!("Grant type '{grant_type}' not allowed for the client."))
+}
+
+fn audience_not_allowed(audience: &str) -> Error {
+    Error::AccessDenied(format!("Client is not authorized to access \"{audience}\"."))
+}
+
+/// The user picked while logging in, if any. The user could have been removed or blocked in the meanwhile
+fn logged_in_user(app_data: &AppData, subject: Option<&str>) -> Result<Option<User>, Error> {
+    match subject {
+        None => Ok(None),
+        Some(subject) => match app_data.users().get(subject)? {
+            None => Err(Error::InvalidGrant(format!("Unknown user '{subject}'"))),
+            Some(user) if user.blocked() => Err(Error::InvalidGrant(format!("User '{subject}' is blocked"))),
+            user => Ok(user),
+        },
+    }
+}
 
-        HttpResponse::Ok()
-            .content_type("application/json")
-            .body(serde_json::to_string(&response).expect("Failed to serialize TokenResponse"))
+/// Unless configured otherwise the management API audience is granted every management API scope
+fn audience_permissions(app_data: &AppData, audience: &str) -> Result<Vec<String>, Error> {
+    let permissions: Vec<String> = app_data.audiences().get_permissions(audience)?;
+
+    if permissions.is_empty() && audience == management::audience(app_data.issuer()) {
+        Ok(management::SCOPES.iter().map(|scope| scope.to_string()).collect())
     } else {
-        HttpResponse::Unauthorized()
-            .content_type("application/json")
-            .body(r#"{"error":"access_denied","error_description":"Unauthorized"}"#)
+        Ok(permissions)
     }
 }
 
-fn new_token_response(
-    app_data: &AppData,
+/// Attributes custom claim templates can refer to
+fn template_context(
+    user: serde_json::Value,
+    client: Option<&Client>,
     audience: &str,
+    grant_type: &GrantType,
+    scope: &str,
+) -> serde_json::Value {
+    serde_json::json!({
+        "user": user,
+        "client": client.map(|client| serde_json::json!({ "id": client.id(), "name": client.name() })),
+        "audience": audience,
+        "grant_type": grant_type.to_string(),
+        "scope": scope,
+    })
+}
+
+/// What the tokens of a token response are issued for
+struct TokenGrant<'a> {
+    client: &'a Client,
+    user: Option<&'a User>,
+    audience: &'a str,
     grant_type: GrantType,
+    scope: Option<&'a str>,
     nonce: Option<String>,
-) -> TokenResponse {
-    let permissions: Vec<String> = app_data
+    lifetime: Option<i64>,
+}
+
+impl<'a> TokenGrant<'a> {
+    fn new(client: &'a Client, audience: &'a str, grant_type: GrantType) -> Self {
+        Self {
+            client,
+            user: None,
+            audience,
+            grant_type,
+            scope: None,
+            nonce: None,
+            lifetime: None,
+        }
+    }
+
+    /// Tokens are issued to the given user or, when missing, to the default one
+    fn with_user(self, user: Option<&'a User>) -> Self {
+        Self { user, ..self }
+    }
+
+    fn with_scope(self, scope: Option<&'a str>) -> Self {
+        Self { scope, ..self }
+    }
+
+    fn with_nonce(self, nonce: Option<String>) -> Self {
+        Self { nonce, ..self }
+    }
+
+    /// Overrides the configured lifetimes of both access and id tokens
+    fn with_lifetime(self, lifetime: Option<i64>) -> Result<Self, Error> {
+        match lifetime {
+            Some(lifetime) if !is_valid_lifetime(lifetime) => Err(Error::InvalidRequest(format!(
+                "Parameter 'expires_in' must be a positive number of seconds, up to {MAX_TOKEN_LIFETIME}"
+            ))),
+            lifetime => Ok(Self { lifetime, ..self }),
+        }
+    }
+}
+
+async fn new_token_response(app_data: &AppData, grant: TokenGrant<'_>) -> Result<TokenResponse, Error> {
+    let TokenGrant {
+        client,
+        user,
+        audience,
+        grant_type,
+        scope,
+        nonce,
+        lifetime,
+    } = grant;
+
+    let roles: Vec<Role> = match user {
+        Some(user) => app_data.roles().get_all(user.roles())?,
+        None => vec![],
+    };
+
+    // Audiences missing from the configuration, e.g. the management API, get the default settings
+    let settings: Audience = app_data
         .audiences()
-        .get_permissions(audience)
-        .expect("Failed to get permissions");
+        .get(audience)?
+        .unwrap_or_else(|| Audience::new(audience.to_string(), vec![], app_data.jwks().default_signing_alg()));
+
+    let access_token_lifetime: i64 = lifetime
+        .or(settings.token_lifetime())
+        .unwrap_or(*app_data.access_token_lifetime());
+
+    let audience_permissions: Vec<String> = audience_permissions(app_data, audience)?;
+
+    // Requested scopes other than the OpenID ones must be permissions of the audience
+    let (openid_scopes, requested_permissions): (Vec<&str>, Vec<&str>) = scope
+        .unwrap_or_default()
+        .split_whitespace()
+        .partition(|scope| OPENID_SCOPES.contains(scope) || *scope == OFFLINE_ACCESS_SCOPE);
+
+    if let Some(unknown) = requested_permissions
+        .iter()
+        .find(|permission| !audience_permissions.iter().any(|p| p == *permission))
+    {
+        return Err(Error::InvalidScope(format!(
+            "Scope '{unknown}' is not defined for audience '{audience}'"
+        )));
+    }
 
-    let custom_claims = app_data
+    let mut permissions: Vec<String> = match (user, settings.enable_rbac()) {
+        (Some(user), true) => user.granted_permissions(audience, audience_permissions, &roles),
+        _ => audience_permissions,
+    };
+
+    // Without requested permissions every granted one is included, as auth0 does
+    if !requested_permissions.is_empty() {
+        permissions.retain(|permission| requested_permissions.contains(&permission.as_str()));
+    }
+
+    // Auth0 RBAC exposes roles through a custom claim, usually added to both tokens by an action
+    let roles_claim: Option<CustomField> = match (user, app_data.roles_claim()) {
+        (Some(_), Some(name)) => Some(CustomField::new(
+            name.to_string(),
+            CustomFieldValue::Vec(roles.iter().map(|role| role.name().to_string()).collect()),
+        )),
+        _ => None,
+    };
+
+    // OpenID scopes requested while logging in are granted along with the permissions
+    let scope: String = openid_scopes
+        .into_iter()
+        .chain(permissions.iter().map(String::as_str))
+        .collect::<Vec<&str>>()
+        .join(" ");
+
+    let mut user_info: UserInfo = match user {
+        Some(user) => user.user_info().clone(),
+        None => app_data.user_info().get()?,
+    };
+
+    let context: serde_json::Value = template_context(
+        match user {
+            Some(user) => serde_json::to_value(user)?,
+            None => serde_json::to_value(&user_info)?,
+        },
+        Some(client),
+        audience,
+        &grant_type,
+        &scope,
+    );
+
+    let subject: String = user.map_or_else(|| app_data.subject().0.to_string(), |user| user.subject().to_string());
+    let applies = |custom_field: &CustomField| custom_field.applies_to(audience, client.id(), &grant_type, &subject);
+
+    let custom_claims: Vec<CustomField> = app_data
         .custom_claims()
-        .all()
-        .expect("Failed to get custom claims")
-        .to_owned();
+        .all()?
+        .into_iter()
+        .filter(|c| applies(c))
+        .collect();
+    let mut custom_claims: Vec<CustomField> = render_custom_fields(&custom_claims, &context)?;
+    custom_claims.extend(roles_claim.clone());
+
+    if let Some(custom_fields) = user_info.custom_fields.take() {
+        let custom_fields: Vec<CustomField> = custom_fields.into_iter().filter(|c| applies(c)).collect();
+        user_info.custom_fields = Some(render_custom_fields(&custom_fields, &context)?);
+    }
 
-    let claims: Claims = Claims::new(
+    // Actions see the proposed tokens through the event, and change them through their `api`
+    let event: serde_json::Value = serde_json::json!({
+        "user": context["user"],
+        "client": { "client_id": client.id(), "name": client.name() },
+        "resource_server": { "identifier": audience },
+        "request": { "grant_type": grant_type.to_string(), "scope": scope },
+        "authorization": { "roles": roles.iter().map(|role| role.name()).collect::<Vec<&str>>() },
+    });
+    let patch: ClaimsPatch = app_data.actions().execute((&grant_type).into(), &event)?;
+    patch.apply_to_access_token(&mut custom_claims, Error::ActionFailed)?;
+    let mut id_token_custom_fields: Vec<CustomField> = user_info.custom_fields.take().unwrap_or_default();
+    id_token_custom_fields.extend(roles_claim.clone());
+    patch.apply_to_id_token(&mut id_token_custom_fields, Error::ActionFailed)?;
+    user_info.custom_fields = Some(id_token_custom_fields);
+
+    let mut claims: Claims = Claims::new(
         audience.to_string(),
+        client.id().to_string(),
         permissions,
         app_data.issuer().0.to_string(),
-        app_data.subject().0.to_string(),
+        subject,
         grant_type,
         custom_claims,
-    );
-
-    let user_info = app_data.user_info().get().expect("Failed to get user info");
-    let id_token_claims: IdTokenClaims =
-        IdTokenClaims::new(app_data.issuer(), CLIENT_ID_VALUE.to_string(), user_info, nonce);
+    )
+    .with_scope(scope.clone())
+    .with_permissions_claim(settings.includes_permissions())
+    .with_lifetime(access_token_lifetime);
+
+    let mut id_token_claims: IdTokenClaims =
+        IdTokenClaims::new(app_data.issuer(), client.id().to_string(), user_info, nonce).with_lifetime(
+            lifetime
+                .or(client.id_token_lifetime())
+                .unwrap_or(*app_data.id_token_lifetime()),
+        );
 
-    let random_jwk: Jwk = app_data.jwks().random_jwk().expect("Failed to get JWK");
-    let access_token: String = random_jwk.encode(&claims).expect("Failed to generate JWT");
-    let id_token: String = random_jwk.encode(&id_token_claims).expect("Failed to generate IdToken");
+    // The client webhook runs first, so that the audience one sees its changes
+    for webhook in [client.webhook(), settings.webhook()].into_iter().flatten() {
+        let body: serde_json::Value = serde_json::json!({
+            "access_token": claims,
+            "id_token": id_token_claims,
+            "event": event,
+        });
+        let patch: ClaimsPatch = webhook.call(app_data.http_client(), &body).await?;
+        patch.apply_to_access_token(claims.custom_claims_mut(), Error::WebhookFailed)?;
+        patch.apply_to_id_token(id_token_claims.custom_fields_mut(), Error::WebhookFailed)?;
+    }
 
-    TokenResponse::new(access_token, id_token, None)
+    let (claims, typ): (Claims, &str) = if settings.token_dialect().is_rfc9068() {
+        (claims.with_client_id(), "at+jwt")
+    } else {
+        (claims, "JWT")
+    };
+    let access_token: String = match settings.signing_secret() {
+        Some(secret) if is_symmetric_alg(settings.signing_alg()) => {
+            encode_with_secret(&claims, settings.signing_alg(), secret, typ)?
+        }
+        _ => app_data
+            .jwks()
+            .signing_jwk_for(settings.signing_alg())?
+            .encode_with_type(&claims, typ)?,
+    };
+    let id_token: String = app_data.jwks().signing_jwk()?.encode(&id_token_claims)?;
+
+    Ok(TokenResponse::new(
+        access_token,
+        id_token,
+        Some(scope),
+        access_token_lifetime,
+    ))
 }
 
 #[cfg(test)]
@@ -280,9 +866,9 @@ mod test {
     use serde_json::json;
     use std::collections::HashMap;
 
-    #[test]
-    fn new_token_response_should_return_an_access_token_and_an_id_token() {
-        use super::new_token_response;
+    #[actix_web::test]
+    async fn new_token_response_should_return_an_access_token_and_an_id_token() {
+        use super::{new_token_response, TokenGrant};
 
         let config_string: &str = r#"
         issuer = "https://prima.localauth0.com/"
@@ -323,7 +909,14 @@ mod test {
         let grant_type = GrantType::AuthorizationCode;
         let nonce = Some("nonce".to_string());
 
-        let token_response = new_token_response(&app_data, audience, grant_type, nonce);
+        let client = app_data.clients().get(CLIENT_ID_VALUE).unwrap().unwrap();
+
+        let token_response = new_token_response(
+            &app_data,
+            TokenGrant::new(&client, audience, grant_type).with_nonce(nonce),
+        )
+        .await
+        .unwrap();
 
         let access_token = token_response.access_token();
         let jwks = app_data.jwks().get().unwrap();
@@ -332,6 +925,7 @@ mod test {
             .expect("failed to parse access_token");
 
         assert_eq!(claims_json.get("aud").unwrap(), audience);
+        assert_eq!(claims_json.get("azp").unwrap(), CLIENT_ID_VALUE);
         assert!(claims_json.get("iat").is_some());
         assert!(claims_json.get("exp").is_some());
         assert!(claims_json.get("scope").is_some());
@@ -369,6 +963,173 @@ mod test {
         assert_eq!(claims_json.get("nonce").unwrap(), "nonce");
     }
 
+    #[actix_web::test]
+    async fn new_token_response_should_honour_audience_settings_test() {
+        use super::{new_token_response, TokenGrant};
+        use crate::error::Error;
+
+        let config: Config = toml::from_str(
+            r#"
+            [[audience]]
+            name = "rbac"
+            permissions = ["rbac:read", "rbac:write"]
+            token_lifetime = 300
+
+            [[audience]]
+            name = "no-rbac"
+            permissions = ["no-rbac:read", "no-rbac:write"]
+            enable_rbac = false
+            add_permissions_in_token = false
+
+            [[audience]]
+            name = "rfc9068"
+            permissions = ["rfc9068:read"]
+            token_dialect = "rfc9068_profile_authz"
+            add_permissions_in_token = false
+
+            [[audience]]
+            name = "ec"
+            signing_alg = "ES256"
+
+            [[audience]]
+            name = "hmac"
+            signing_alg = "HS256"
+
+            [[user]]
+            subject = "auth0|alice"
+            name = "Alice"
+            permissions = []
+            "#,
+        )
+        .unwrap();
+        let app_data = AppData::new(&config).unwrap();
+        let client = app_data.clients().get(CLIENT_ID_VALUE).unwrap().unwrap();
+        let user = app_data.users().get("auth0|alice").unwrap().unwrap();
+
+        let decode = |jwt: &str, part: usize| -> serde_json::Value {
+            let part: &str = jwt.split('.').nth(part).unwrap();
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(part).unwrap()).unwrap()
+        };
+
+        let response = new_token_response(
+            &app_data,
+            TokenGrant::new(&client, "rbac", GrantType::AuthorizationCode).with_user(Some(&user)),
+        )
+        .await
+        .unwrap();
+        let claims = decode(response.access_token(), 1);
+        assert_eq!(claims["permissions"], json!([]));
+        assert_eq!(claims["exp"].as_i64().unwrap() - claims["iat"].as_i64().unwrap(), 300);
+
+        let response = new_token_response(
+            &app_data,
+            TokenGrant::new(&client, "no-rbac", GrantType::AuthorizationCode).with_user(Some(&user)),
+        )
+        .await
+        .unwrap();
+        let claims = decode(response.access_token(), 1);
+        assert!(claims.get("permissions").is_none());
+        assert_eq!(claims["scope"], "no-rbac:read no-rbac:write");
+
+        let response = new_token_response(
+            &app_data,
+            TokenGrant::new(&client, "rfc9068", GrantType::ClientCredentials),
+        )
+        .await
+        .unwrap();
+        assert_eq!(decode(response.access_token(), 0)["typ"], "at+jwt");
+        let claims = decode(response.access_token(), 1);
+        assert_eq!(claims["client_id"], CLIENT_ID_VALUE);
+        assert_eq!(claims["permissions"], json!(["rfc9068:read"]));
+
+        let response = new_token_response(&app_data, TokenGrant::new(&client, "ec", GrantType::ClientCredentials))
+            .await
+            .unwrap();
+        assert_eq!(decode(response.access_token(), 0)["alg"], "ES256");
+        assert_eq!(decode(response.id_token(), 0)["alg"], "RS256");
+
+        let response = new_token_response(
+            &app_data,
+            TokenGrant::new(&client, "hmac", GrantType::ClientCredentials),
+        )
+        .await
+        .unwrap();
+        let header = decode(response.access_token(), 0);
+        assert_eq!(header["alg"], "HS256");
+        assert!(header.get("kid").is_none());
+        let hmac = app_data.audiences().get("hmac").unwrap().unwrap();
+        let mut validation = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
+        validation.set_audience(&["hmac"]);
+        let decoding_key = jsonwebtoken::DecodingKey::from_secret(hmac.signing_secret().unwrap().as_bytes());
+        assert!(jsonwebtoken::decode::<serde_json::Value>(response.access_token(), &decoding_key, &validation).is_ok());
+
+        // Audiences switched at runtime to an algorithm without keys can't be signed for
+        let mut ec = app_data.audiences().get("ec").unwrap().unwrap();
+        ec.set_signing_alg(jsonwebtoken::Algorithm::PS512);
+        app_data.audiences().put(ec).unwrap();
+        let result = new_token_response(&app_data, TokenGrant::new(&client, "ec", GrantType::ClientCredentials)).await;
+        assert!(matches!(result, Err(Error::MissingSigningKey(_))));
+    }
+
+    #[actix_web::test]
+    async fn new_token_response_should_derive_expirations_from_lifetimes_test() {
+        use super::{new_token_response, TokenGrant};
+
+        let config: Config = toml::from_str(
+            r#"
+            [access_token]
+            lifetime = 7200
+
+            [id_token]
+            lifetime = 1800
+
+            [[audience]]
+            name = "short-lived"
+            token_lifetime = 60
+
+            [[client]]
+            id = "client_id"
+            secret = "client_secret"
+            id_token_lifetime = 900
+            "#,
+        )
+        .unwrap();
+        let app_data = AppData::new(&config).unwrap();
+        let client = app_data.clients().get(CLIENT_ID_VALUE).unwrap().unwrap();
+
+        async fn lifetimes(app_data: &AppData, grant: TokenGrant<'_>) -> (i64, i64, i64) {
+            let response = new_token_response(app_data, grant).await.unwrap();
+            let lifetime = |jwt: &str| -> i64 {
+                let payload: &str = jwt.split('.').nth(1).unwrap();
+                let claims: serde_json::Value =
+                    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+                claims["exp"].as_i64().unwrap() - claims["iat"].as_i64().unwrap()
+            };
+            (
+                response.expires_in(),
+                lifetime(response.access_token()),
+                lifetime(response.id_token()),
+            )
+        }
+
+        let grant = TokenGrant::new(&client, "audience", GrantType::ClientCredentials);
+        assert_eq!(lifetimes(&app_data, grant).await, (7200, 7200, 900));
+
+        let grant = TokenGrant::new(&client, "short-lived", GrantType::ClientCredentials);
+        assert_eq!(lifetimes(&app_data, grant).await, (60, 60, 900));
+
+        let grant = TokenGrant::new(&client, "short-lived", GrantType::ClientCredentials)
+            .with_lifetime(Some(5))
+            .unwrap();
+        assert_eq!(lifetimes(&app_data, grant).await, (5, 5, 5));
+
+        let grant = TokenGrant::new(&client, "audience", GrantType::ClientCredentials).with_lifetime(Some(0));
+        assert!(grant.is_err());
+
+        let grant = TokenGrant::new(&client, "audience", GrantType::ClientCredentials).with_lifetime(Some(i64::MAX));
+        assert!(grant.is_err());
+    }
+
     #[actix_web::test]
     async fn healthcheck_test() {
         use super::healthcheck;
@@ -408,7 +1169,64 @@ mod test {
 
         let req = test::TestRequest::get().uri(jwks::ENDPOINT).to_request();
         let resp: Jwks = test::call_and_read_body_json(&app, req).await;
-        assert_eq!(resp.keys.len(), 3);
+        assert_eq!(resp.keys.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn signing_secret_test() {
+        use super::{get_signing_secret, jwks};
+        use actix_web::{body, test, web::Data, App};
+
+        let config: Config = toml::from_str(
+            r#"
+            [[audience]]
+            name = "legacy"
+            signing_alg = "HS256"
+            signing_secret = "a-shared-secret-of-at-least-32-bytes"
+
+            [[audience]]
+            name = "generated"
+            signing_alg = "HS512"
+
+            [[audience]]
+            name = "asymmetric"
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(get_signing_secret)
+                .service(jwks),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/audiences/legacy/signing_secret")
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["signing_secret"], "a-shared-secret-of-at-least-32-bytes");
+
+        let req = test::TestRequest::get()
+            .uri("/audiences/generated/signing_secret")
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let generated: String = resp["signing_secret"].as_str().unwrap().to_string();
+        assert_eq!(generated.len(), 43);
+
+        let req = test::TestRequest::get()
+            .uri("/audiences/asymmetric/signing_secret")
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let req = test::TestRequest::get().uri(jwks::ENDPOINT).to_request();
+        let bytes = body::to_bytes(test::call_service(&app, req).await.into_body())
+            .await
+            .unwrap();
+        let published = String::from_utf8(bytes.to_vec()).unwrap();
+        assert!(!published.contains("a-shared-secret-of-at-least-32-bytes"));
+        assert!(!published.contains(&generated));
+        assert!(!published.contains("HS256"));
     }
 
     #[actix_web::test]
@@ -456,6 +1274,34 @@ mod test {
         assert_eq!(resp, vec!["world"]);
     }
 
+    #[actix_web::test]
+    async fn audiences_created_at_runtime_sign_with_the_default_alg_test() {
+        use super::{set_permissions_for_audience, token};
+        use crate::management::tests::token_request;
+        use crate::model::TokenResponse;
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config: Config = toml::from_str("[jwks]\nsigning_algs = [\"ES256\"]").unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(set_permissions_for_audience)
+                .service(token),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/permissions")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "audience": "hello", "permissions": ["world"] }).to_string())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let resp: TokenResponse = test::call_and_read_body_json(&app, token_request("hello").to_request()).await;
+        let header: jsonwebtoken::Header = jsonwebtoken::decode_header(resp.access_token()).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::ES256);
+    }
+
     #[actix_web::test]
     async fn custom_claims_test() {
         use super::{get_custom_claims, set_custom_claims, token};
@@ -523,6 +1369,8 @@ mod test {
             client_id: "client_id".to_string(),
             client_secret: "client_secret".to_string(),
             audience: "test_audience".to_string(),
+            scope: None,
+            expires_in: None,
         });
 
         let req = test::TestRequest::post()
@@ -613,6 +1461,8 @@ mod test {
             client_id: "client_id".to_string(),
             client_secret: "client_secret".to_string(),
             audience: "test_audience".to_string(),
+            scope: None,
+            expires_in: None,
         });
 
         let req = test::TestRequest::post()
@@ -638,6 +1488,1255 @@ mod test {
         assert_eq!(user_info["custom_field2"], "value2");
     }
 
+    #[actix_web::test]
+    async fn configured_clients_test() {
+        use super::token;
+        use crate::model::TokenResponse;
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[client]]
+        id = "backend"
+        secret = "backend_secret"
+        grant_types = ["client_credentials"]
+        audiences = ["audience1"]
+
+        [[client]]
+        id = "frontend"
+        secret = "frontend_secret"
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token),
+        )
+        .await;
+
+        let token_request = |client_id: &str, client_secret: &str, audience: &str| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "client_credentials",
+                        "client_id": client_id,
+                        "client_secret": client_secret,
+                        "audience": audience,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("backend", "backend_secret", "audience1")).await;
+        assert_eq!(extract_payload(resp.access_token())["azp"], "backend");
+        assert_eq!(extract_payload(resp.id_token())["aud"], "backend");
+
+        let resp = test::call_service(&app, token_request("backend", "wrong_secret", "audience1")).await;
+        assert_eq!(resp.status(), 401);
+
+        // The default client stays registered for the web frontend
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request(CLIENT_ID_VALUE, "client_secret", "audience1")).await;
+        assert_eq!(extract_payload(resp.access_token())["azp"], CLIENT_ID_VALUE);
+
+        let resp = test::call_service(&app, token_request("backend", "backend_secret", "audience2")).await;
+        assert_eq!(resp.status(), 403);
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("frontend", "frontend_secret", "audience2")).await;
+        assert_eq!(extract_payload(resp.access_token())["azp"], "frontend");
+    }
+
+    #[actix_web::test]
+    async fn confidential_clients_test() {
+        use super::{login, token};
+        use crate::model::{LoginRequest, LoginResponse, TokenResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[client]]
+        id = "backend"
+        secret = "backend_secret"
+
+        [[client]]
+        id = "spa"
+        secret = "spa_secret"
+        public = true
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token),
+        )
+        .await;
+
+        let login_request = |client_id: &str| {
+            let login_request = LoginRequest {
+                audience: "audience1".to_string(),
+                client_id: Some(client_id.to_string()),
+                redirect_uri: None,
+                scope: Some("openid offline_access".to_string()),
+                nonce: None,
+                state: None,
+                code_challenge: None,
+                code_challenge_method: None,
+                user: None,
+            };
+            test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(serde_json::to_string(&login_request).unwrap())
+                .to_request()
+        };
+        let token_request = |mut body: serde_json::Value, client_secret: Option<&str>| {
+            if let Some(client_secret) = client_secret {
+                body["client_secret"] = json!(client_secret);
+            }
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(body.to_string())
+                .to_request()
+        };
+
+        // Confidential clients must authenticate on every grant
+        let LoginResponse { code } = test::call_and_read_body_json(&app, login_request("backend")).await;
+        let code_request = json!({ "grant_type": "authorization_code", "client_id": "backend", "code": code });
+        let resp = test::call_service(&app, token_request(code_request.clone(), None)).await;
+        assert_eq!(resp.status(), 401);
+
+        let LoginResponse { code } = test::call_and_read_body_json(&app, login_request("backend")).await;
+        let code_request = json!({ "grant_type": "authorization_code", "client_id": "backend", "code": code });
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request(code_request, Some("backend_secret"))).await;
+        let refresh_token = resp.refresh_token().expect("missing refresh token").to_string();
+
+        let refresh_request =
+            json!({ "grant_type": "refresh_token", "client_id": "backend", "refresh_token": refresh_token });
+        let resp = test::call_service(&app, token_request(refresh_request.clone(), None)).await;
+        assert_eq!(resp.status(), 401);
+        let resp = test::call_service(&app, token_request(refresh_request, Some("backend_secret"))).await;
+        assert_eq!(resp.status(), 200);
+
+        // Public clients may omit their secret, but a wrong one is still rejected
+        let LoginResponse { code } = test::call_and_read_body_json(&app, login_request("spa")).await;
+        let code_request = json!({ "grant_type": "authorization_code", "client_id": "spa", "code": code });
+        let resp = test::call_service(&app, token_request(code_request.clone(), Some("wrong_secret"))).await;
+        assert_eq!(resp.status(), 401);
+        let resp: TokenResponse = test::call_and_read_body_json(&app, token_request(code_request, None)).await;
+        let refresh_token = resp.refresh_token().expect("missing refresh token").to_string();
+
+        let refresh_request =
+            json!({ "grant_type": "refresh_token", "client_id": "spa", "refresh_token": refresh_token });
+        let resp = test::call_service(&app, token_request(refresh_request, None)).await;
+        assert_eq!(resp.status(), 200);
+    }
+
+    #[actix_web::test]
+    async fn refresh_token_test() {
+        use super::{login, token};
+        use crate::model::{LoginRequest, LoginResponse, TokenResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token),
+        )
+        .await;
+
+        let login_request = LoginRequest {
+            audience: "audience1".to_string(),
+            client_id: None,
+            redirect_uri: None,
+            scope: Some("openid offline_access".to_string()),
+            nonce: None,
+            state: None,
+            code_challenge: None,
+            code_challenge_method: None,
+            user: None,
+        };
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(ContentType::json())
+            .set_payload(serde_json::to_string(&login_request).unwrap())
+            .to_request();
+        let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code }).to_string(),
+            )
+            .to_request();
+        let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+        let first_refresh_token = resp.refresh_token().expect("missing refresh token").to_string();
+
+        let refresh_request_expiring_in = |refresh_token: &str, expires_in: Option<i64>| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "refresh_token",
+                        "client_id": CLIENT_ID_VALUE,
+                        "refresh_token": refresh_token,
+                        "expires_in": expires_in,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+        let refresh_request = |refresh_token: &str| refresh_request_expiring_in(refresh_token, None);
+
+        // A failed exchange leaves the refresh token usable
+        let resp = test::call_service(&app, refresh_request_expiring_in(&first_refresh_token, Some(0))).await;
+        assert_eq!(resp.status(), 400);
+
+        let resp: TokenResponse = test::call_and_read_body_json(&app, refresh_request(&first_refresh_token)).await;
+        let claims = extract_payload(resp.access_token());
+        assert_eq!(claims["aud"], "audience1");
+        assert_eq!(claims["gty"], "refresh_token");
+        let second_refresh_token = resp.refresh_token().expect("missing rotated refresh token").to_string();
+        assert_ne!(first_refresh_token, second_refresh_token);
+
+        // Reusing the first refresh token revokes the whole family
+        let resp = test::call_service(&app, refresh_request(&first_refresh_token)).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_grant");
+
+        let resp = test::call_service(&app, refresh_request(&second_refresh_token)).await;
+        assert_eq!(resp.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn pkce_test() {
+        use super::{login, token};
+        use crate::model::{CodeChallengeMethod, LoginRequest, LoginResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        // Values taken from <https://datatracker.ietf.org/doc/html/rfc7636#appendix-B>
+        let code_verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let code_challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token),
+        )
+        .await;
+
+        for (code_verifier, expected_status) in [(Some(code_verifier), 200), (Some("wrong"), 400), (None, 400)] {
+            let login_request = LoginRequest {
+                audience: "audience1".to_string(),
+                client_id: None,
+                redirect_uri: None,
+                scope: None,
+                nonce: None,
+                state: None,
+                code_challenge: Some(code_challenge.to_string()),
+                code_challenge_method: Some(CodeChallengeMethod::S256),
+                user: None,
+            };
+            let req = test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(serde_json::to_string(&login_request).unwrap())
+                .to_request();
+            let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+            let req = test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "authorization_code",
+                        "client_id": CLIENT_ID_VALUE,
+                        "code": code,
+                        "code_verifier": code_verifier,
+                    })
+                    .to_string(),
+                )
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), expected_status);
+
+            if expected_status == 400 {
+                let body: serde_json::Value = test::read_body_json(resp).await;
+                assert_eq!(body["error"], "invalid_grant");
+            }
+        }
+    }
+
+    #[actix_web::test]
+    async fn authorization_code_binding_test() {
+        use super::{login, token};
+        use crate::model::{LoginResponse, TokenResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token),
+        )
+        .await;
+
+        let login_request = || {
+            test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "audience": "audience1",
+                        "client_id": CLIENT_ID_VALUE,
+                        "redirect_uri": "http://localhost:8080/callback",
+                        "nonce": "nonce",
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let token_request = |code: &str, client_id: &str, redirect_uri: &str| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "authorization_code",
+                        "client_id": client_id,
+                        "code": code,
+                        "redirect_uri": redirect_uri,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let LoginResponse { code } = test::call_and_read_body_json(&app, login_request()).await;
+        let resp = test::call_service(
+            &app,
+            token_request(&code, CLIENT_ID_VALUE, "http://localhost:8080/other"),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+
+        let LoginResponse { code } = test::call_and_read_body_json(&app, login_request()).await;
+        let resp: TokenResponse = test::call_and_read_body_json(
+            &app,
+            token_request(&code, CLIENT_ID_VALUE, "http://localhost:8080/callback"),
+        )
+        .await;
+        assert_eq!(extract_payload(resp.id_token())["nonce"], "nonce");
+
+        // Codes are single use
+        let resp = test::call_service(
+            &app,
+            token_request(&code, CLIENT_ID_VALUE, "http://localhost:8080/callback"),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_grant");
+    }
+
+    #[actix_web::test]
+    async fn token_errors_test() {
+        use super::token;
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token),
+        )
+        .await;
+
+        let cases = [
+            (json!({ "client_id": CLIENT_ID_VALUE }), 400, "invalid_request"),
+            (json!({ "grant_type": "password" }), 400, "unsupported_grant_type"),
+            (json!({ "grant_type": "client_credentials" }), 400, "invalid_request"),
+            (
+                json!({ "grant_type": "client_credentials", "client_id": CLIENT_ID_VALUE, "client_secret": "wrong", "audience": "audience" }),
+                401,
+                "invalid_client",
+            ),
+            (
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": "unknown" }),
+                400,
+                "invalid_grant",
+            ),
+        ];
+
+        for (payload, status, error) in cases {
+            let req = test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(payload.to_string())
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), status);
+
+            let body: serde_json::Value = test::read_body_json(resp).await;
+            assert_eq!(body["error"], error);
+            assert!(body["error_description"].is_string());
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::form_url_encoded())
+            .set_payload("grant_type=client_credentials&client_id=client_id&client_secret=client_secret&audience=aud")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn userinfo_test() {
+        use super::{login, token, userinfo};
+        use crate::model::{LoginResponse, TokenResponse};
+        use actix_web::{http::header, http::header::ContentType, test, web::Data, App};
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token)
+                .service(userinfo),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "audience": "audience1", "scope": "openid email" }).to_string())
+            .to_request();
+        let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code }).to_string(),
+            )
+            .to_request();
+        let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::get()
+            .uri("/userinfo")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["sub"], "google-apps|developers@prima.it");
+        assert_eq!(body["email"], "developers@prima.it");
+        assert_eq!(body["email_verified"], true);
+        assert!(body.get("name").is_none());
+
+        let req = test::TestRequest::post()
+            .uri("/userinfo")
+            .insert_header((header::AUTHORIZATION, "Bearer invalid"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401);
+        assert!(resp.headers().get(header::WWW_AUTHENTICATE).is_some());
+
+        let req = test::TestRequest::get().uri("/userinfo").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 401);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_token");
+    }
+
+    #[actix_web::test]
+    async fn userinfo_with_symmetric_audience_test() {
+        use super::{login, token, userinfo};
+        use crate::model::{encode_with_secret, LoginResponse, TokenResponse};
+        use actix_web::{http::header, http::header::ContentType, test, web::Data, App};
+
+        let config: Config = toml::from_str(
+            r#"
+            [[audience]]
+            name = "legacy"
+            signing_alg = "HS256"
+            signing_secret = "a-shared-secret-of-at-least-32-bytes"
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token)
+                .service(userinfo),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "audience": "legacy", "scope": "openid email" }).to_string())
+            .to_request();
+        let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code }).to_string(),
+            )
+            .to_request();
+        let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::get()
+            .uri("/userinfo")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(body["email"], "developers@prima.it");
+
+        // Same claims, signed with another secret
+        let claims: serde_json::Value = extract_payload(resp.access_token());
+        let forged: String = encode_with_secret(
+            &claims,
+            jsonwebtoken::Algorithm::HS256,
+            "another-secret-of-at-least-32-bytes",
+            "JWT",
+        )
+        .unwrap();
+        let req = test::TestRequest::get()
+            .uri("/userinfo")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {forged}")))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+    }
+
+    #[actix_web::test]
+    async fn discovery_test() {
+        use super::{oauth_authorization_server, openid_configuration};
+        use actix_web::{test, web::Data, App};
+
+        let config = Config::default();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(openid_configuration)
+                .service(oauth_authorization_server),
+        )
+        .await;
+
+        let req = test::TestRequest::get()
+            .uri("/.well-known/openid-configuration")
+            .to_request();
+        let discovery: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(discovery["authorization_endpoint"], "http://localhost:8080/authorize");
+        assert_eq!(discovery["userinfo_endpoint"], "http://localhost:8080/userinfo");
+        assert_eq!(discovery["response_types_supported"], json!(["code", "token"]));
+        assert_eq!(discovery["code_challenge_methods_supported"], json!(["S256", "plain"]));
+        for grant_type in ["authorization_code", "client_credentials", "refresh_token"] {
+            assert!(discovery["grant_types_supported"]
+                .as_array()
+                .unwrap()
+                .contains(&json!(grant_type)));
+        }
+        for claim in ["sub", "nonce", "email", "name"] {
+            assert!(discovery["claims_supported"]
+                .as_array()
+                .unwrap()
+                .contains(&json!(claim)));
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/.well-known/oauth-authorization-server")
+            .to_request();
+        let authorization_server: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+
+        assert_eq!(discovery, authorization_server);
+
+        // The default client is public, so the ones authenticating without a secret are supported
+        assert_eq!(
+            discovery["token_endpoint_auth_methods_supported"],
+            json!(["client_secret_post", "none"])
+        );
+
+        let config: Config = toml::from_str(
+            r#"
+            [[client]]
+            id = "client_id"
+            secret = "client_secret"
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(openid_configuration),
+        )
+        .await;
+        let req = test::TestRequest::get()
+            .uri("/.well-known/openid-configuration")
+            .to_request();
+        let discovery: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            discovery["token_endpoint_auth_methods_supported"],
+            json!(["client_secret_post"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn users_test() {
+        use super::{get_users, login, token, userinfo};
+        use crate::model::{LoginResponse, TokenResponse};
+        use actix_web::{http::header, http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[audience]]
+        name = "audience1"
+        permissions = ["audience1:read", "audience1:write"]
+
+        [[user]]
+        subject = "auth0|admin"
+        name = "Admin"
+
+        [[user]]
+        subject = "auth0|reader"
+        name = "Reader"
+        email = "reader@example.com"
+        permissions = ["audience1:read"]
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(get_users)
+                .service(login)
+                .service(token)
+                .service(userinfo),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/users").to_request();
+        let users: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(users[0]["sub"], "auth0|admin");
+        assert_eq!(users[1]["sub"], "auth0|reader");
+        assert_eq!(users[1]["permissions"], json!(["audience1:read"]));
+
+        let login_request = |user: &str| {
+            test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(json!({ "audience": "audience1", "scope": "openid email", "user": user }).to_string())
+                .to_request()
+        };
+        let token_request = |code: &str| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code })
+                        .to_string(),
+                )
+                .to_request()
+        };
+
+        let resp = test::call_service(&app, login_request("auth0|unknown")).await;
+        assert_eq!(resp.status(), 400);
+
+        for (user, permissions) in [
+            ("auth0|admin", json!(["audience1:read", "audience1:write"])),
+            ("auth0|reader", json!(["audience1:read"])),
+        ] {
+            let LoginResponse { code } = test::call_and_read_body_json(&app, login_request(user)).await;
+            let resp: TokenResponse = test::call_and_read_body_json(&app, token_request(&code)).await;
+
+            let access_token = extract_payload(resp.access_token());
+            assert_eq!(access_token["sub"], user);
+            assert_eq!(access_token["permissions"], permissions);
+            assert_eq!(extract_payload(resp.id_token())["sub"], user);
+
+            let req = test::TestRequest::get()
+                .uri("/userinfo")
+                .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+                .to_request();
+            let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(body["sub"], user);
+        }
+    }
+
+    #[actix_web::test]
+    async fn roles_test() {
+        use super::{assign_user_roles, delete_role, get_user_roles, login, set_role, token};
+        use crate::model::{LoginResponse, TokenResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[audience]]
+        name = "audience1"
+        permissions = ["audience1:read", "audience1:write"]
+
+        [[role]]
+        name = "reader"
+        permissions = { audience1 = ["audience1:read"] }
+
+        [[user]]
+        subject = "auth0|user"
+        name = "User"
+        roles = ["reader"]
+
+        [access_token]
+        roles_claim = "https://localauth0/roles"
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(assign_user_roles)
+                .service(delete_role)
+                .service(get_user_roles)
+                .service(login)
+                .service(set_role)
+                .service(token),
+        )
+        .await;
+
+        let issue_token = || async {
+            let req = test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(json!({ "audience": "audience1", "user": "auth0|user" }).to_string())
+                .to_request();
+            let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+            let req = test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code })
+                        .to_string(),
+                )
+                .to_request();
+            let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+            (extract_payload(resp.access_token()), extract_payload(resp.id_token()))
+        };
+
+        let (access_token, id_token) = issue_token().await;
+        assert_eq!(access_token["permissions"], json!(["audience1:read"]));
+        assert_eq!(access_token["https://localauth0/roles"], json!(["reader"]));
+        assert_eq!(id_token["https://localauth0/roles"], json!(["reader"]));
+
+        let req = test::TestRequest::post()
+            .uri("/roles")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "name": "writer", "permissions": { "audience1": ["audience1:write"] } }).to_string())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::post()
+            .uri("/users/auth0%7Cuser/roles")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "roles": ["unknown"] }).to_string())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/users/auth0%7Cuser/roles")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "roles": ["writer"] }).to_string())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let (access_token, _) = issue_token().await;
+        assert_eq!(
+            access_token["permissions"],
+            json!(["audience1:read", "audience1:write"])
+        );
+        assert_eq!(access_token["https://localauth0/roles"], json!(["reader", "writer"]));
+
+        let req = test::TestRequest::delete().uri("/roles/reader").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/users/auth0%7Cuser/roles").to_request();
+        let roles: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            roles,
+            json!([{ "name": "writer", "permissions": { "audience1": ["audience1:write"] } }])
+        );
+
+        let req = test::TestRequest::get()
+            .uri("/users/auth0%7Cunknown/roles")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 404);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "not_found");
+
+        // Removing the last role leaves the user without permissions
+        let req = test::TestRequest::delete().uri("/roles/writer").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let (access_token, _) = issue_token().await;
+        assert_eq!(access_token["permissions"], json!([]));
+    }
+
+    #[actix_web::test]
+    async fn scope_test() {
+        use super::{login, token};
+        use crate::model::LoginResponse;
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[audience]]
+        name = "audience1"
+        permissions = ["audience1:read", "audience1:write"]
+
+        [[user]]
+        subject = "auth0|reader"
+        name = "Reader"
+        permissions = ["audience1:read"]
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token),
+        )
+        .await;
+
+        let client_credentials = |scope: Option<&str>| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "client_credentials",
+                        "client_id": CLIENT_ID_VALUE,
+                        "client_secret": crate::CLIENT_SECRET_VALUE,
+                        "audience": "audience1",
+                        "scope": scope,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, client_credentials(None)).await;
+        assert_eq!(resp["scope"], "audience1:read audience1:write");
+
+        let resp: serde_json::Value =
+            test::call_and_read_body_json(&app, client_credentials(Some("audience1:write"))).await;
+        assert_eq!(resp["scope"], "audience1:write");
+        let access_token = extract_payload(resp["access_token"].as_str().unwrap());
+        assert_eq!(access_token["scope"], "audience1:write");
+        assert_eq!(access_token["permissions"], json!(["audience1:write"]));
+
+        let resp = test::call_service(&app, client_credentials(Some("audience1:write audience1:delete"))).await;
+        assert_eq!(resp.status(), 400);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_scope");
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "audience": "audience1", "user": "auth0|reader", "scope": "openid audience1:read audience1:write" })
+                    .to_string(),
+            )
+            .to_request();
+        let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code }).to_string(),
+            )
+            .to_request();
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["scope"], "openid audience1:read");
+        assert_eq!(
+            extract_payload(resp["access_token"].as_str().unwrap())["permissions"],
+            json!(["audience1:read"])
+        );
+    }
+
+    #[actix_web::test]
+    async fn custom_claim_templates_test() {
+        use super::{login, set_custom_claims, token};
+        use crate::model::{LoginResponse, TokenResponse};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[user]]
+        subject = "auth0|alice"
+        name = "Alice"
+        email = "alice@example.com"
+        app_metadata = { tenant = { id = "acme" } }
+        custom_fields = [
+            { name = "https://example.com/greeting", value = { String = "Hi {{user.name}} from {{client.id}}" } }
+        ]
+
+        [access_token]
+        custom_claims = [
+            { name = "https://example.com/email", value = { String = "{{user.email}}" } },
+            { name = "https://example.com/tenant", value = { String = "{{user.app_metadata.tenant}}" } },
+            { name = "https://example.com/plan", value = { String = "{{user.app_metadata.plan | default: \"free\"}}" } },
+            { name = "https://example.com/kind", value = { Json = { kind = "{{#if grant_type == \"client_credentials\"}}m2m{{else}}user{{/if}}", audience = "{{audience}}" } } },
+        ]
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(set_custom_claims)
+                .service(token),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(ContentType::json())
+            .set_payload(json!({ "audience": "audience1", "user": "auth0|alice" }).to_string())
+            .to_request();
+        let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code }).to_string(),
+            )
+            .to_request();
+        let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+
+        let access_token = extract_payload(resp.access_token());
+        assert_eq!(access_token["https://example.com/email"], "alice@example.com");
+        assert_eq!(access_token["https://example.com/tenant"], json!({ "id": "acme" }));
+        assert_eq!(access_token["https://example.com/plan"], "free");
+        assert_eq!(
+            access_token["https://example.com/kind"],
+            json!({ "kind": "user", "audience": "audience1" })
+        );
+
+        let id_token = extract_payload(resp.id_token());
+        assert_eq!(id_token["https://example.com/greeting"], "Hi Alice from client_id");
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token/custom_claims")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "custom_claims": [{ "name": "broken", "value": { "String": "{{#if user.email}}" } }] })
+                    .to_string(),
+            )
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+
+    #[actix_web::test]
+    async fn scoped_custom_claims_test() {
+        use super::{get_custom_claims, set_custom_claims, token};
+        use crate::model::TokenResponse;
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [access_token]
+        custom_claims = [
+            { name = "global", value = { String = "global" } },
+            { name = "https://api1/claim", value = { String = "api1" }, audiences = ["api1"] },
+            { name = "m2m", value = { Bool = true }, grant_types = ["client_credentials"] },
+            { name = "other_client", value = { Bool = true }, clients = ["other"] },
+        ]
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(get_custom_claims)
+                .service(set_custom_claims)
+                .service(token),
+        )
+        .await;
+
+        let access_token = |audience: &'static str| {
+            let app = &app;
+            async move {
+                let req = test::TestRequest::post()
+                    .uri("/oauth/token")
+                    .insert_header(ContentType::json())
+                    .set_payload(
+                        json!({
+                            "grant_type": "client_credentials",
+                            "client_id": CLIENT_ID_VALUE,
+                            "client_secret": crate::CLIENT_SECRET_VALUE,
+                            "audience": audience,
+                        })
+                        .to_string(),
+                    )
+                    .to_request();
+                let resp: TokenResponse = test::call_and_read_body_json(app, req).await;
+                extract_payload(resp.access_token())
+            }
+        };
+
+        let claims = access_token("api1").await;
+        assert_eq!(claims["global"], "global");
+        assert_eq!(claims["https://api1/claim"], "api1");
+        assert_eq!(claims["m2m"], true);
+        assert!(claims.get("other_client").is_none());
+
+        let claims = access_token("api2").await;
+        assert_eq!(claims["global"], "global");
+        assert!(claims.get("https://api1/claim").is_none());
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/token/custom_claims")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "custom_claims": [{ "name": "api2_only", "value": { "String": "api2" }, "audiences": ["api2"] }] })
+                    .to_string(),
+            )
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/oauth/token/custom_claims").to_request();
+        let custom_claims: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            custom_claims,
+            json!([{ "name": "api2_only", "value": { "String": "api2" }, "audiences": ["api2"] }])
+        );
+
+        assert!(access_token("api1").await.get("api2_only").is_none());
+        assert_eq!(access_token("api2").await["api2_only"], "api2");
+    }
+
+    #[actix_web::test]
+    async fn actions_test() {
+        use super::{delete_action, get_actions, set_action, token};
+        use actix_web::{http::header::ContentType, test, web::Data, App};
+
+        let config_string: &str = r#"
+        [[action]]
+        name = "tenant"
+        trigger = "credentials-exchange"
+        script = """
+            api.accessToken.setCustomClaim("https://example.com/tenant", event.client.client_id);
+            if event.resource_server.identifier == "blocked" {
+                api.access.deny("audience not allowed");
+            }
+        """
+        "#;
+        let config: Config = toml::from_str(config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(get_actions)
+                .service(set_action)
+                .service(delete_action)
+                .service(token),
+        )
+        .await;
+
+        let request_token = |audience: &'static str| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "client_credentials",
+                        "client_id": CLIENT_ID_VALUE,
+                        "client_secret": crate::CLIENT_SECRET_VALUE,
+                        "audience": audience,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, request_token("audience")).await;
+        let claims = extract_payload(resp["access_token"].as_str().unwrap());
+        assert_eq!(claims["https://example.com/tenant"], CLIENT_ID_VALUE);
+
+        let resp = test::call_service(&app, request_token("blocked")).await;
+        assert_eq!(resp.status(), 403);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "access_denied");
+        assert_eq!(body["error_description"], "audience not allowed");
+
+        let req = test::TestRequest::post()
+            .uri("/actions")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({ "name": "broken", "trigger": "credentials-exchange", "script": "throw \"boom\";" }).to_string(),
+            )
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get().uri("/actions").to_request();
+        let actions: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(actions.as_array().unwrap().len(), 2);
+
+        let resp = test::call_service(&app, request_token("audience")).await;
+        assert_eq!(resp.status(), 500);
+
+        let req = test::TestRequest::delete().uri("/actions/broken").to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+        let req = test::TestRequest::delete().uri("/actions/broken").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+
+        let resp = test::call_service(&app, request_token("audience")).await;
+        assert!(resp.status().is_success());
+    }
+
+    #[actix_web::test]
+    async fn webhooks_test() {
+        use super::token;
+        use actix_web::{http::header::ContentType, test, web, web::Data, App, HttpResponse, HttpServer};
+
+        let server = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/client",
+                    web::post().to(|_: web::Json<serde_json::Value>| async {
+                        HttpResponse::Ok().json(json!({ "access_token": { "client_claim": "client" } }))
+                    }),
+                )
+                .route(
+                    "/audience",
+                    web::post().to(|body: web::Json<serde_json::Value>| async move {
+                        if body["event"]["resource_server"]["identifier"] == "denied" {
+                            return HttpResponse::Forbidden()
+                                .json(json!({ "error": "access_denied", "error_description": "nope" }));
+                        }
+                        HttpResponse::Ok().json(json!({
+                            "access_token": { "seen": body["access_token"]["client_claim"], "client_claim": null },
+                            "id_token": { "https://example.com/sub": body["id_token"]["sub"] },
+                        }))
+                    }),
+                )
+                // Handlers read the body, otherwise the connection gets closed under the pooled client
+                .route(
+                    "/broken",
+                    web::post()
+                        .to(|_: web::Json<serde_json::Value>| async { HttpResponse::InternalServerError().finish() }),
+                )
+                .route(
+                    "/missing",
+                    web::post().to(|_: web::Json<serde_json::Value>| async { HttpResponse::NotFound().finish() }),
+                )
+                .route(
+                    "/reserved",
+                    web::post().to(|_: web::Json<serde_json::Value>| async {
+                        HttpResponse::Ok().json(json!({ "access_token": { "sub": "someone" } }))
+                    }),
+                )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let port: u16 = server.addrs()[0].port();
+        actix_web::rt::spawn(server.run());
+
+        let config_string: String = format!(
+            r#"
+            [[client]]
+            id = "client_id"
+            secret = "client_secret"
+            webhook = {{ url = "http://127.0.0.1:{port}/client" }}
+
+            [[audience]]
+            name = "enriched"
+            webhook = {{ url = "http://127.0.0.1:{port}/audience" }}
+
+            [[audience]]
+            name = "denied"
+            webhook = {{ url = "http://127.0.0.1:{port}/audience" }}
+
+            [[audience]]
+            name = "fail-closed"
+            webhook = {{ url = "http://127.0.0.1:{port}/broken" }}
+
+            [[audience]]
+            name = "fail-open"
+            webhook = {{ url = "http://127.0.0.1:{port}/broken", timeout = 1000, fail_open = true }}
+
+            [[audience]]
+            name = "missing"
+            webhook = {{ url = "http://127.0.0.1:{port}/missing", fail_open = true }}
+
+            [[audience]]
+            name = "reserved"
+            webhook = {{ url = "http://127.0.0.1:{port}/reserved" }}
+            "#
+        );
+        let config: Config = toml::from_str(&config_string).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token),
+        )
+        .await;
+
+        let request_token = |audience: &'static str| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({
+                        "grant_type": "client_credentials",
+                        "client_id": CLIENT_ID_VALUE,
+                        "client_secret": crate::CLIENT_SECRET_VALUE,
+                        "audience": audience,
+                    })
+                    .to_string(),
+                )
+                .to_request()
+        };
+
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, request_token("enriched")).await;
+        let claims = extract_payload(resp["access_token"].as_str().unwrap());
+        assert_eq!(claims["seen"], "client");
+        assert!(claims.get("client_claim").is_none());
+        let claims = extract_payload(resp["id_token"].as_str().unwrap());
+        assert_eq!(claims["https://example.com/sub"], claims["sub"]);
+
+        let resp = test::call_service(&app, request_token("denied")).await;
+        assert_eq!(resp.status(), 403);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error_description"], "nope");
+
+        let resp = test::call_service(&app, request_token("fail-closed")).await;
+        assert_eq!(resp.status(), 500);
+
+        let resp: serde_json::Value = test::call_and_read_body_json(&app, request_token("fail-open")).await;
+        let claims = extract_payload(resp["access_token"].as_str().unwrap());
+        assert_eq!(claims["client_claim"], "client");
+
+        // A 4xx without an oauth error body is a failure of the webhook, not a denial
+        let resp = test::call_service(&app, request_token("missing")).await;
+        assert_eq!(resp.status(), 200);
+
+        let resp = test::call_service(&app, request_token("reserved")).await;
+        assert_eq!(resp.status(), 500);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["error_description"].as_str().unwrap().contains("'sub'"));
+    }
+
     fn extract_payload(token: &str) -> serde_json::Value {
         let parts: Vec<&str> = token.split('.').collect();
         let v = URL_SAFE_NO_PAD.decode(parts[1]).unwrap();
@@ -1,8 +1,12 @@
 use std::string::FromUtf8Error;
 
+use actix_web::http::{header, StatusCode};
+use actix_web::{HttpResponse, ResponseError};
 use openssl::error::ErrorStack;
 use thiserror::Error;
 
+use crate::BEARER;
+
 #[derive(Debug, Error)]
 pub enum Error {
     #[error("Provided kid '{0}' is none of in well known jwks kid")]
@@ -14,6 +18,58 @@ pub enum Error {
     #[error("Provided JWT does not contain a KID")]
     JwtMissingKid,
 
+    #[error("No signing key available for the {0:?} algorithm")]
+    MissingSigningKey(jsonwebtoken::Algorithm),
+
+    #[error("Keys for the {0:?} algorithm can't be published in the jwks")]
+    UnsupportedSigningAlg(jsonwebtoken::Algorithm),
+
+    #[error("Invalid signing key: {0}")]
+    InvalidSigningKey(String),
+
+    #[error("{0}")]
+    InvalidRequest(String),
+
+    #[error("{0}")]
+    InvalidClient(String),
+
+    #[error("{0}")]
+    InvalidGrant(String),
+
+    #[error("{0}")]
+    UnauthorizedClient(String),
+
+    #[error("{0}")]
+    UnsupportedGrantType(String),
+
+    #[error("{0}")]
+    InvalidScope(String),
+
+    #[error("{0}")]
+    AccessDenied(String),
+
+    /// Missing, expired or otherwise invalid bearer token. See <https://datatracker.ietf.org/doc/html/rfc6750#section-3.1>
+    #[error("{0}")]
+    InvalidToken(String),
+
+    /// The bearer token lacks the scope required by the endpoint. See <https://datatracker.ietf.org/doc/html/rfc6750#section-3.1>
+    #[error("{0}")]
+    InsufficientScope(String),
+
+    #[error("{0}")]
+    NotFound(String),
+
+    #[error("{0}")]
+    Conflict(String),
+
+    /// An action script failed while issuing a token
+    #[error("{0}")]
+    ActionFailed(String),
+
+    /// The enrichment webhook failed while issuing a token
+    #[error("{0}")]
+    WebhookFailed(String),
+
     #[error(transparent)]
     JWTError(#[from] jsonwebtoken::errors::Error),
 
@@ -25,4 +81,79 @@ pub enum Error {
 
     #[error(transparent)]
     OpenSSLParseUtf8Error(#[from] FromUtf8Error),
+
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+}
+
+impl Error {
+    /// Error of a token request denied by an action or a webhook with the given error code
+    pub fn denied(error_code: &str, description: String) -> Self {
+        match error_code {
+            "invalid_request" => Error::InvalidRequest(description),
+            "invalid_scope" => Error::InvalidScope(description),
+            "unauthorized_client" => Error::UnauthorizedClient(description),
+            _ => Error::AccessDenied(description),
+        }
+    }
+
+    /// Error code as defined in <https://datatracker.ietf.org/doc/html/rfc6749#section-5.2>
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            Error::InvalidRequest(_) => "invalid_request",
+            Error::InvalidClient(_) => "invalid_client",
+            Error::InvalidGrant(_) => "invalid_grant",
+            Error::UnauthorizedClient(_) => "unauthorized_client",
+            Error::UnsupportedGrantType(_) => "unsupported_grant_type",
+            Error::InvalidScope(_) => "invalid_scope",
+            Error::AccessDenied(_) => "access_denied",
+            Error::InvalidToken(_) => "invalid_token",
+            Error::InsufficientScope(_) => "insufficient_scope",
+            Error::NotFound(_) => "not_found",
+            Error::Conflict(_) => "conflict",
+            _ => "server_error",
+        }
+    }
+}
+
+impl ResponseError for Error {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            Error::InvalidRequest(_)
+            | Error::InvalidGrant(_)
+            | Error::UnauthorizedClient(_)
+            | Error::UnsupportedGrantType(_)
+            | Error::InvalidScope(_) => StatusCode::BAD_REQUEST,
+            Error::InvalidClient(_) | Error::InvalidToken(_) => StatusCode::UNAUTHORIZED,
+            Error::AccessDenied(_) | Error::InsufficientScope(_) => StatusCode::FORBIDDEN,
+            Error::NotFound(_) => StatusCode::NOT_FOUND,
+            Error::Conflict(_) => StatusCode::CONFLICT,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        if self.status_code().is_server_error() {
+            tracing::error!("{}", self);
+        }
+
+        let mut response = HttpResponse::build(self.status_code());
+
+        if let Error::InvalidToken(description) | Error::InsufficientScope(description) = self {
+            response.insert_header((
+                header::WWW_AUTHENTICATE,
+                format!(
+                    r#"{} error="{}", error_description="{}""#,
+                    BEARER,
+                    self.error_code(),
+                    description.replace('"', "'")
+                ),
+            ));
+        }
+
+        response.json(serde_json::json!({
+            "error": self.error_code(),
+            "error_description": self.to_string(),
+        }))
+    }
 }
@@ -2,6 +2,7 @@ mod app_data;
 pub mod config;
 pub mod controller;
 pub mod error;
+pub mod management;
 pub mod model;
 mod store;
 
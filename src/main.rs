@@ -11,8 +11,12 @@ use openssl::ssl::{SslAcceptor, SslAcceptorBuilder, SslMethod};
 
 use localauth0::config::Config;
 use localauth0::controller;
+use localauth0::error::Error as LocalAuth0Error;
+use localauth0::management;
 use localauth0::model::{certificates, AppData};
 
+const PURGE_INTERVAL: Duration = Duration::from_secs(60);
+
 fn main() -> Result<(), Box<dyn Error>> {
     match std::env::args().nth(1).as_deref() {
         Some("healthcheck") => Ok(healthcheck()?),
@@ -71,12 +75,69 @@ async fn server() -> std::io::Result<()> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    actix_web::rt::spawn(purge_expired(data.clone()));
+
+    if let Some(rotation_interval) = config.jwks().rotation_interval() {
+        actix_web::rt::spawn(rotate_keys(
+            data.clone(),
+            Duration::from_secs(rotation_interval.get()),
+            config.jwks().rotation_overlap().map(Duration::from_secs),
+        ));
+    }
+
     let http_server = start_http_server(data.clone(), *config.http().port());
     let https_server = start_https_server(data, *config.https().port());
 
     futures::try_join!(http_server, https_server).map(|_| ())
 }
 
+/// Removes the authorization codes and the refresh tokens which can't be exchanged anymore
+async fn purge_expired(data: Data<AppData>) {
+    let mut interval = actix_web::rt::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(error) = data.authorizations().purge_expired() {
+            tracing::error!("Failed to purge expired authorizations: {}", error);
+        }
+
+        if let Err(error) = data.refresh_tokens().purge_expired() {
+            tracing::error!("Failed to purge expired refresh tokens: {}", error);
+        }
+    }
+}
+
+/// Rotates the keys every `rotation_interval`. With an overlap the previous keys are retired once it elapses, otherwise
+/// they stay published until the following rotation.
+async fn rotate_keys(data: Data<AppData>, rotation_interval: Duration, overlap: Option<Duration>) {
+    let start = actix_web::rt::time::Instant::now() + rotation_interval;
+    let mut interval = actix_web::rt::time::interval_at(start, rotation_interval);
+
+    loop {
+        interval.tick().await;
+
+        match data.jwks().rotate_keys() {
+            Ok(()) => tracing::info!("Signing keys rotated"),
+            Err(error) => {
+                tracing::error!("Failed to rotate the signing keys: {}", error);
+                continue;
+            }
+        }
+
+        if let Some(overlap) = overlap {
+            let data: Data<AppData> = data.clone();
+            actix_web::rt::spawn(async move {
+                actix_web::rt::time::sleep(overlap).await;
+
+                if let Err(error) = data.jwks().retire_previous_keys(overlap) {
+                    tracing::error!("Failed to retire the previous signing keys: {}", error);
+                }
+            });
+        }
+    }
+}
+
 fn start_http_server(data: Data<AppData>, port: u16) -> impl Future<Output = Result<(), std::io::Error>> {
     HttpServer::new(move || {
         App::new()
@@ -104,20 +165,46 @@ fn start_https_server(data: Data<AppData>, port: u16) -> impl Future<Output = Re
 }
 
 fn setup_service(cfg: &mut web::ServiceConfig) {
+    // Malformed requests are answered with an oauth `invalid_request` error
+    cfg.app_data(
+        web::JsonConfig::default().error_handler(|error, _| LocalAuth0Error::InvalidRequest(error.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default().error_handler(|error, _| LocalAuth0Error::InvalidRequest(error.to_string()).into()),
+    )
+    .app_data(
+        web::QueryConfig::default().error_handler(|error, _| LocalAuth0Error::InvalidRequest(error.to_string()).into()),
+    );
+
     cfg.service(controller::healthcheck)
         .service(controller::jwks)
         .service(controller::get_permissions)
         .service(controller::set_permissions_for_audience)
         .service(controller::get_permissions_by_audience)
+        .service(controller::get_signing_secret)
         .service(controller::get_custom_claims)
         .service(controller::set_custom_claims)
         .service(controller::get_user_info)
         .service(controller::set_user_info)
+        .service(controller::get_users)
+        .service(controller::get_user_roles)
+        .service(controller::assign_user_roles)
+        .service(controller::remove_user_roles)
+        .service(controller::get_roles)
+        .service(controller::set_role)
+        .service(controller::get_role)
+        .service(controller::delete_role)
+        .service(controller::get_actions)
+        .service(controller::set_action)
+        .service(controller::delete_action)
         .service(controller::rotate_keys)
         .service(controller::revoke_keys)
         .service(controller::login)
         .service(controller::token)
         .service(controller::openid_configuration)
+        .service(controller::oauth_authorization_server)
+        .service(controller::userinfo)
+        .configure(management::configure)
         .service(
             Files::new("/", "./web/dist")
                 .index_file("index.html")
@@ -168,4 +255,23 @@ mod tests {
         let resp = test::call_service(&app, req).await;
         assert_ne!(resp.status(), 404, "User info POST endpoint not registered");
     }
+
+    #[actix_web::test]
+    async fn test_malformed_requests_are_answered_with_invalid_request() {
+        let config = Config::load_or_default();
+        let app_data = Data::new(AppData::new(&config).expect("Failed to create AppData"));
+
+        let app = test::init_service(App::new().app_data(app_data).configure(setup_service)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/oauth/login")
+            .insert_header(actix_web::http::header::ContentType::json())
+            .set_payload("{}")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 400);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_request");
+    }
 }
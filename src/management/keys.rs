@@ -0,0 +1,177 @@
+use actix_web::web::{Data, Path};
+use actix_web::{get, post, put, HttpRequest, HttpResponse};
+
+use super::model::{SigningKeyCertificate, SigningKeyResponse};
+use super::{authorize, CREATE_SIGNING_KEYS, READ_SIGNING_KEYS, UPDATE_SIGNING_KEYS};
+use crate::error::Error;
+use crate::model::{AppData, Jwk};
+
+/// Keys of every signing algorithm, revoked ones included.
+/// See <https://auth0.com/docs/api/management/v2/keys/get-signing-keys>
+#[get("/keys/signing")]
+pub async fn get_signing_keys(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_SIGNING_KEYS)?;
+
+    let keys: Vec<SigningKeyResponse> = app_data
+        .jwks()
+        .all()?
+        .iter()
+        .map(SigningKeyResponse::try_from)
+        .collect::<Result<_, Error>>()?;
+
+    Ok(HttpResponse::Ok().json(keys))
+}
+
+/// Rotates the keys of every signing algorithm and returns the new current key of the default one.
+/// See <https://auth0.com/docs/api/management/v2/keys/post-signing-keys>
+#[post("/keys/signing/rotate")]
+pub async fn rotate_signing_key(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, CREATE_SIGNING_KEYS)?;
+
+    app_data.jwks().rotate_keys()?;
+    let jwk: Jwk = app_data.jwks().signing_jwk()?;
+
+    Ok(HttpResponse::Created().json(SigningKeyCertificate::try_from(&jwk)?))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/keys/get-signing-key>
+#[get("/keys/signing/{kid}")]
+pub async fn get_signing_key(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    kid: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_SIGNING_KEYS)?;
+
+    let jwk: Jwk = app_data
+        .jwks()
+        .all()?
+        .into_iter()
+        .find(|jwk| jwk.kid() == kid.as_str())
+        .ok_or_else(|| Error::NotFound(format!("Signing key '{kid}' not found")))?;
+
+    Ok(HttpResponse::Ok().json(SigningKeyResponse::try_from(&jwk)?))
+}
+
+/// The current key can't be revoked, the next and previous ones stop being published.
+/// See <https://auth0.com/docs/api/management/v2/keys/put-signing-keys>
+#[put("/keys/signing/{kid}/revoke")]
+pub async fn revoke_signing_key(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    kid: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_SIGNING_KEYS)?;
+
+    let jwk: Jwk = app_data.jwks().revoke_key(&kid)?;
+
+    Ok(HttpResponse::Ok().json(SigningKeyCertificate::try_from(&jwk)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::{test, web::Data, App};
+    use serde_json::Value;
+
+    use crate::config::Config;
+    use crate::controller::{jwks, token};
+    use crate::management;
+    use crate::management::tests::token_request;
+    use crate::model::{AppData, Jwks, TokenResponse};
+
+    #[actix_web::test]
+    async fn signing_keys_test() {
+        let config: Config = toml::from_str(r#"issuer = "https://localauth0.test/""#).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .service(jwks)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+        let signing_keys = || {
+            test::TestRequest::get()
+                .uri("/api/v2/keys/signing")
+                .insert_header(authorization.clone())
+                .to_request()
+        };
+
+        let keys: Vec<Value> = test::call_and_read_body_json(&app, signing_keys()).await;
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[0]["current"], true);
+        assert_eq!(keys[1]["next"], true);
+        assert!(keys[0]["cert"]
+            .as_str()
+            .unwrap()
+            .starts_with("-----BEGIN CERTIFICATE-----"));
+        assert_eq!(keys[0]["thumbprint"].as_str().unwrap().len(), 40);
+        assert!(keys[0]["current_since"].is_string());
+        let (current, next) = (keys[0]["kid"].clone(), keys[1]["kid"].clone());
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/keys/signing/rotate")
+            .insert_header(authorization.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+        let rotated: Value = test::read_body_json(resp).await;
+        assert_eq!(rotated["kid"], next);
+
+        // Current, next and previous keys are published in this order
+        let req = test::TestRequest::get().uri("/.well-known/jwks.json").to_request();
+        let published: Jwks = test::call_and_read_body_json(&app, req).await;
+        let kids: Vec<Value> = published.keys.iter().map(|jwk| Value::from(jwk.kid())).collect();
+        assert_eq!(kids.len(), 3);
+        assert_eq!(kids[0], next);
+        assert_eq!(kids[2], current);
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/v2/keys/signing/{}/revoke", next.as_str().unwrap()))
+            .insert_header(authorization.clone())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/api/v2/keys/signing/{}/revoke", current.as_str().unwrap()))
+            .insert_header(authorization.clone())
+            .to_request();
+        let revoked: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(revoked["kid"], current);
+
+        // Tokens signed with a revoked key are rejected
+        let req = test::TestRequest::get()
+            .uri("/api/v2/keys/signing")
+            .insert_header(authorization.clone())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v2/keys/signing/{}", current.as_str().unwrap()))
+            .insert_header(authorization.clone())
+            .to_request();
+        let key: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(key["revoked"], true);
+        assert!(key["revoked_at"].is_string());
+        assert!(key["current_until"].is_string());
+
+        let req = test::TestRequest::get().uri("/.well-known/jwks.json").to_request();
+        let published: Jwks = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(published.keys.len(), 2);
+        assert!(published.find(current.as_str().unwrap().to_string()).is_none());
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/keys/signing/missing")
+            .insert_header(authorization.clone())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+}
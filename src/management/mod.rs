@@ -0,0 +1,172 @@
+//! Emulation of the Auth0 Management API v2, see <https://auth0.com/docs/api/management/v2>.
+//!
+//! Endpoints require a bearer token issued by localauth0 for the management API audience, granted the scope
+//! of the endpoint. Unless permissions are configured for that audience every management API scope is granted,
+//! so that a token can be obtained through the `client_credentials` grant.
+use actix_web::web::{self, ServiceConfig};
+use actix_web::HttpRequest;
+
+use crate::controller::bearer_token;
+use crate::error::Error;
+use crate::model::{AppData, Claims, Issuer};
+
+mod keys;
+mod model;
+mod resource_servers;
+mod roles;
+mod users;
+
+pub const READ_USERS: &str = "read:users";
+pub const CREATE_USERS: &str = "create:users";
+pub const UPDATE_USERS: &str = "update:users";
+pub const DELETE_USERS: &str = "delete:users";
+pub const READ_ROLES: &str = "read:roles";
+pub const CREATE_ROLES: &str = "create:roles";
+pub const UPDATE_ROLES: &str = "update:roles";
+pub const DELETE_ROLES: &str = "delete:roles";
+pub const READ_RESOURCE_SERVERS: &str = "read:resource_servers";
+pub const CREATE_RESOURCE_SERVERS: &str = "create:resource_servers";
+pub const UPDATE_RESOURCE_SERVERS: &str = "update:resource_servers";
+pub const DELETE_RESOURCE_SERVERS: &str = "delete:resource_servers";
+pub const READ_SIGNING_KEYS: &str = "read:signing_keys";
+pub const CREATE_SIGNING_KEYS: &str = "create:signing_keys";
+pub const UPDATE_SIGNING_KEYS: &str = "update:signing_keys";
+
+/// Scopes granted by default to the management API audience
+pub const SCOPES: [&str; 15] = [
+    READ_USERS,
+    CREATE_USERS,
+    UPDATE_USERS,
+    DELETE_USERS,
+    READ_ROLES,
+    CREATE_ROLES,
+    UPDATE_ROLES,
+    DELETE_ROLES,
+    READ_RESOURCE_SERVERS,
+    CREATE_RESOURCE_SERVERS,
+    UPDATE_RESOURCE_SERVERS,
+    DELETE_RESOURCE_SERVERS,
+    READ_SIGNING_KEYS,
+    CREATE_SIGNING_KEYS,
+    UPDATE_SIGNING_KEYS,
+];
+
+/// Audience of the tokens accepted by the management API, `https://{tenant}/api/v2/` on Auth0
+pub fn audience(issuer: &Issuer) -> String {
+    format!("{}/api/v2/", issuer.0.trim_end_matches('/'))
+}
+
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v2")
+            .service(users::get_users)
+            .service(users::create_user)
+            .service(users::get_user)
+            .service(users::update_user)
+            .service(users::delete_user)
+            .service(users::get_users_by_email)
+            .service(users::get_user_roles)
+            .service(users::assign_user_roles)
+            .service(users::remove_user_roles)
+            .service(roles::get_roles)
+            .service(roles::create_role)
+            .service(roles::get_role)
+            .service(roles::update_role)
+            .service(roles::delete_role)
+            .service(roles::get_role_permissions)
+            .service(roles::add_role_permissions)
+            .service(roles::remove_role_permissions)
+            .service(resource_servers::get_resource_servers)
+            .service(resource_servers::create_resource_server)
+            .service(resource_servers::get_resource_server)
+            .service(resource_servers::update_resource_server)
+            .service(resource_servers::delete_resource_server)
+            .service(keys::get_signing_keys)
+            .service(keys::rotate_signing_key)
+            .service(keys::get_signing_key)
+            .service(keys::revoke_signing_key),
+    );
+}
+
+/// Validates the bearer token of the request, which must have been granted the given scope
+fn authorize(app_data: &AppData, req: &HttpRequest, scope: &str) -> Result<Claims, Error> {
+    let access_token: &str = bearer_token(req)?;
+
+    let claims: Claims = app_data.verify_access_token(access_token, &audience(app_data.issuer()))?;
+
+    if claims.scope().split_whitespace().any(|granted| granted == scope) {
+        Ok(claims)
+    } else {
+        Err(Error::InsufficientScope(format!(
+            "Insufficient scope, expected any of: {scope}"
+        )))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use actix_web::http::header::{self, ContentType};
+    use actix_web::{test, web::Data, App};
+    use serde_json::json;
+
+    use crate::config::Config;
+    use crate::controller::token;
+    use crate::model::{AppData, TokenResponse};
+    use crate::{CLIENT_ID_VALUE, CLIENT_SECRET_VALUE};
+
+    /// Client credentials token request for the given audience
+    pub fn token_request(audience: &str) -> test::TestRequest {
+        test::TestRequest::post()
+            .uri("/oauth/token")
+            .insert_header(ContentType::json())
+            .set_payload(
+                json!({
+                    "grant_type": "client_credentials",
+                    "client_id": CLIENT_ID_VALUE,
+                    "client_secret": CLIENT_SECRET_VALUE,
+                    "audience": audience,
+                })
+                .to_string(),
+            )
+    }
+
+    #[actix_web::test]
+    async fn symmetric_management_api_tokens_are_accepted_test() {
+        let config: Config = toml::from_str(
+            r#"
+            issuer = "https://localauth0.test/"
+
+            [[audience]]
+            name = "https://localauth0.test/api/v2/"
+            signing_alg = "HS256"
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .configure(super::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let header: jsonwebtoken::Header = jsonwebtoken::decode_header(resp.access_token()).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::HS256);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/roles")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 200);
+
+        // Tokens of other audiences are still rejected
+        let resp: TokenResponse = test::call_and_read_body_json(&app, token_request("audience1").to_request()).await;
+        let req = test::TestRequest::get()
+            .uri("/api/v2/roles")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+    }
+}
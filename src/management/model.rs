@@ -0,0 +1,407 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use jsonwebtoken::Algorithm;
+use openssl::hash::MessageDigest;
+use openssl::x509::X509;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value};
+
+use crate::error::Error;
+use crate::model::{is_valid_lifetime, Audience, Jwk, KeyState, Role, TokenDialect, User, MAX_TOKEN_LIFETIME};
+
+/// Auth0 database connection, the only one emulated
+const CONNECTION: &str = "Username-Password-Authentication";
+
+#[derive(Deserialize)]
+pub struct ListUsersQuery {
+    #[serde(default)]
+    pub page: usize,
+    pub per_page: Option<usize>,
+    #[serde(default)]
+    pub include_totals: bool,
+}
+
+#[derive(Deserialize)]
+pub struct UsersByEmailQuery {
+    pub email: String,
+}
+
+/// Unknown fields, like `connection` and `password`, are ignored
+#[derive(Deserialize)]
+pub struct CreateUserRequest {
+    pub user_id: Option<String>,
+    pub email: String,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub nickname: Option<String>,
+    pub picture: Option<String>,
+    pub blocked: Option<bool>,
+    pub app_metadata: Option<Map<String, Value>>,
+    pub user_metadata: Option<Map<String, Value>>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+    pub name: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub nickname: Option<String>,
+    pub picture: Option<String>,
+    pub blocked: Option<bool>,
+    pub app_metadata: Option<Map<String, Value>>,
+    pub user_metadata: Option<Map<String, Value>>,
+}
+
+#[derive(Serialize)]
+pub struct UserResponse {
+    user_id: String,
+    email: String,
+    email_verified: bool,
+    name: String,
+    given_name: String,
+    family_name: String,
+    nickname: String,
+    picture: String,
+    created_at: String,
+    updated_at: String,
+    identities: Vec<Identity>,
+    app_metadata: Map<String, Value>,
+    user_metadata: Map<String, Value>,
+    blocked: bool,
+}
+
+#[derive(Serialize)]
+struct Identity {
+    connection: String,
+    provider: String,
+    user_id: String,
+    #[serde(rename = "isSocial")]
+    is_social: bool,
+}
+
+impl From<&User> for UserResponse {
+    fn from(user: &User) -> Self {
+        let user_info = user.user_info();
+        // Auth0 user ids are made of the identity provider and the id given by the provider
+        let (provider, user_id) = user.subject().split_once('|').unwrap_or(("auth0", user.subject()));
+
+        Self {
+            user_id: user.subject().to_string(),
+            email: user_info.email.clone(),
+            email_verified: user_info.email_verified,
+            name: user_info.name.clone(),
+            given_name: user_info.given_name.clone(),
+            family_name: user_info.family_name.clone(),
+            nickname: user_info.nickname.clone(),
+            picture: user_info.picture.clone(),
+            created_at: format_date(user.created_at()),
+            updated_at: format_date(&user_info.updated_at),
+            identities: vec![Identity {
+                connection: CONNECTION.to_string(),
+                provider: provider.to_string(),
+                user_id: user_id.to_string(),
+                is_social: false,
+            }],
+            app_metadata: user.app_metadata().clone(),
+            user_metadata: user.user_metadata().clone(),
+            blocked: user.blocked(),
+        }
+    }
+}
+
+/// Returned listing users with `include_totals=true`
+#[derive(Serialize)]
+pub struct UsersPage {
+    pub start: usize,
+    pub limit: usize,
+    pub length: usize,
+    pub total: usize,
+    pub users: Vec<UserResponse>,
+}
+
+#[derive(Serialize)]
+pub struct RoleResponse {
+    id: String,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+/// Roles are identified by their name
+impl From<&Role> for RoleResponse {
+    fn from(role: &Role) -> Self {
+        Self {
+            id: role.name().to_string(),
+            name: role.name().to_string(),
+            description: role.description().map(str::to_string),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateRoleRequest {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateRoleRequest {
+    pub name: Option<String>,
+    /// `Some(None)` when explicitly `null`, which clears the description
+    #[serde(default, deserialize_with = "nullable")]
+    pub description: Option<Option<String>>,
+}
+
+/// Assigns roles to a user, or removes them
+#[derive(Deserialize)]
+pub struct RolesRequest {
+    pub roles: Vec<String>,
+}
+
+/// Adds permissions to a role, or removes them
+#[derive(Deserialize)]
+pub struct PermissionsRequest {
+    pub permissions: Vec<Permission>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Permission {
+    pub resource_server_identifier: String,
+    pub permission_name: String,
+}
+
+#[derive(Serialize)]
+pub struct ResourceServerResponse {
+    id: String,
+    name: String,
+    identifier: String,
+    scopes: Vec<Scope>,
+    signing_alg: Algorithm,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    signing_secret: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_lifetime: Option<i64>,
+    enforce_policies: bool,
+    token_dialect: TokenDialect,
+}
+
+impl From<&Audience> for ResourceServerResponse {
+    /// Resource servers are identified by their identifier, that is the audience
+    fn from(audience: &Audience) -> Self {
+        // Auth0 tells the permissions claim apart through the `_authz` dialects only
+        let token_dialect: TokenDialect = match (audience.token_dialect(), audience.includes_permissions()) {
+            (TokenDialect::AccessToken, true) => TokenDialect::AccessTokenAuthz,
+            (TokenDialect::Rfc9068Profile, true) => TokenDialect::Rfc9068ProfileAuthz,
+            (token_dialect, _) => token_dialect,
+        };
+
+        Self {
+            id: audience.identifier().to_string(),
+            name: audience.name().to_string(),
+            identifier: audience.identifier().to_string(),
+            scopes: audience
+                .permissions()
+                .iter()
+                .map(|permission| Scope {
+                    value: permission.to_string(),
+                    description: None,
+                })
+                .collect(),
+            signing_alg: audience.signing_alg(),
+            signing_secret: audience.signing_secret().map(str::to_string),
+            token_lifetime: audience.token_lifetime(),
+            enforce_policies: audience.enable_rbac(),
+            token_dialect,
+        }
+    }
+}
+
+/// See <https://auth0.com/docs/api/management/v2/keys/get-signing-keys>
+#[derive(Serialize)]
+pub struct SigningKeyResponse {
+    kid: String,
+    cert: String,
+    fingerprint: String,
+    thumbprint: String,
+    current: bool,
+    next: bool,
+    previous: bool,
+    revoked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_since: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    current_until: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<String>,
+}
+
+impl TryFrom<&Jwk> for SigningKeyResponse {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self, Self::Error> {
+        let certificate: X509 = jwk.certificate()?;
+        let digest: Vec<String> = certificate
+            .digest(MessageDigest::sha1())?
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect();
+        let state: KeyState = jwk.lifecycle().state;
+
+        Ok(Self {
+            kid: jwk.kid().to_string(),
+            cert: String::from_utf8(certificate.to_pem()?)?,
+            fingerprint: digest.join(":"),
+            thumbprint: digest.concat().to_lowercase(),
+            current: state == KeyState::Current,
+            next: state == KeyState::Next,
+            previous: state == KeyState::Previous,
+            revoked: state == KeyState::Revoked,
+            current_since: jwk.lifecycle().current_since.as_ref().map(format_date),
+            current_until: jwk.lifecycle().current_until.as_ref().map(format_date),
+            revoked_at: jwk.lifecycle().revoked_at.as_ref().map(format_date),
+        })
+    }
+}
+
+/// Key returned by the rotation and the revocation, see
+/// <https://auth0.com/docs/api/management/v2/keys/post-signing-keys>
+#[derive(Serialize)]
+pub struct SigningKeyCertificate {
+    kid: String,
+    cert: String,
+}
+
+impl TryFrom<&Jwk> for SigningKeyCertificate {
+    type Error = Error;
+
+    fn try_from(jwk: &Jwk) -> Result<Self, Self::Error> {
+        Ok(Self {
+            kid: jwk.kid().to_string(),
+            cert: String::from_utf8(jwk.certificate()?.to_pem()?)?,
+        })
+    }
+}
+
+/// Descriptions are accepted but not stored
+#[derive(Serialize, Deserialize)]
+pub struct Scope {
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CreateResourceServerRequest {
+    pub identifier: String,
+    #[serde(flatten)]
+    pub settings: ResourceServerSettings,
+}
+
+/// Settings shared by creation and update, missing ones are left untouched
+#[derive(Deserialize)]
+pub struct ResourceServerSettings {
+    pub name: Option<String>,
+    pub scopes: Option<Vec<Scope>>,
+    pub signing_alg: Option<Algorithm>,
+    pub signing_secret: Option<String>,
+    pub token_lifetime: Option<i64>,
+    pub enforce_policies: Option<bool>,
+    pub token_dialect: Option<TokenDialect>,
+}
+
+impl ResourceServerSettings {
+    pub fn apply(self, audience: &mut Audience) -> Result<(), Error> {
+        if let Some(token_lifetime) = self.token_lifetime.filter(|lifetime| !is_valid_lifetime(*lifetime)) {
+            return Err(Error::InvalidRequest(format!(
+                "token_lifetime {token_lifetime} must be a positive number of seconds, up to {MAX_TOKEN_LIFETIME}"
+            )));
+        }
+
+        if let Some(name) = self.name {
+            audience.set_name(Some(name));
+        }
+        if let Some(scopes) = self.scopes {
+            audience.set_permissions(scopes.into_iter().map(|scope| scope.value).collect());
+        }
+        // The secret goes first, so that switching to an HS algorithm doesn't generate one
+        if let Some(signing_secret) = self.signing_secret {
+            audience.set_signing_secret(Some(signing_secret));
+        }
+        if let Some(signing_alg) = self.signing_alg {
+            audience.set_signing_alg(signing_alg);
+        }
+        if let Some(token_lifetime) = self.token_lifetime {
+            audience.set_token_lifetime(Some(token_lifetime));
+        }
+        if let Some(enforce_policies) = self.enforce_policies {
+            audience.set_enable_rbac(enforce_policies);
+        }
+        if let Some(token_dialect) = self.token_dialect {
+            audience.set_token_dialect(token_dialect);
+            audience.set_add_permissions_in_token(token_dialect.is_authz());
+        }
+
+        Ok(())
+    }
+}
+
+fn format_date(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Metadata are merged one level deep: top level properties are replaced and a `null` property is removed. An
+/// empty object clears the metadata.
+/// See <https://auth0.com/docs/manage-users/user-accounts/metadata/manage-metadata-api#update-user-metadata>
+/// Tells an explicit `null` apart from a missing field, left `None` by `#[serde(default)]`
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+pub fn merge_metadata(metadata: &mut Map<String, Value>, patch: Map<String, Value>) {
+    if patch.is_empty() {
+        metadata.clear();
+    }
+
+    for (key, value) in patch {
+        if value.is_null() {
+            metadata.remove(&key);
+        } else {
+            metadata.insert(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use super::merge_metadata;
+
+    fn map(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn metadata_are_merged_one_level_deep() {
+        let mut metadata = map(json!({ "plan": "free", "address": { "city": "Milan", "zip": "20100" }, "beta": true }));
+
+        merge_metadata(
+            &mut metadata,
+            map(json!({ "plan": "pro", "address": { "city": "Rome" }, "beta": null })),
+        );
+        assert_eq!(
+            Value::Object(metadata.clone()),
+            json!({ "plan": "pro", "address": { "city": "Rome" } })
+        );
+
+        merge_metadata(&mut metadata, Map::new());
+        assert!(metadata.is_empty());
+    }
+}
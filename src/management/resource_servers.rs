@@ -0,0 +1,275 @@
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse};
+use jsonwebtoken::Algorithm;
+
+use super::model::{CreateResourceServerRequest, ResourceServerResponse, ResourceServerSettings};
+use super::{
+    authorize, CREATE_RESOURCE_SERVERS, DELETE_RESOURCE_SERVERS, READ_RESOURCE_SERVERS, UPDATE_RESOURCE_SERVERS,
+};
+use crate::error::Error;
+use crate::model::{is_symmetric_alg, AppData, Audience};
+
+/// Resource servers are the configured audiences, their scopes the audience permissions.
+/// See <https://auth0.com/docs/api/management/v2/resource-servers/get-resource-servers>
+#[get("/resource-servers")]
+pub async fn get_resource_servers(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_RESOURCE_SERVERS)?;
+
+    let resource_servers: Vec<ResourceServerResponse> = app_data
+        .audiences()
+        .list()?
+        .iter()
+        .map(ResourceServerResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(resource_servers))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/resource-servers/post-resource-servers>
+#[post("/resource-servers")]
+pub async fn create_resource_server(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    request: Json<CreateResourceServerRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, CREATE_RESOURCE_SERVERS)?;
+
+    let CreateResourceServerRequest { identifier, settings } = request.into_inner();
+
+    if app_data.audiences().get(&identifier)?.is_some() {
+        return Err(Error::Conflict(
+            "A resource server with the same identifier already exists.".to_string(),
+        ));
+    }
+
+    let mut audience: Audience = Audience::new(identifier, vec![], app_data.jwks().default_signing_alg());
+    settings.apply(&mut audience)?;
+    check_signing_alg(&app_data, &audience)?;
+    app_data.audiences().put(audience.clone())?;
+
+    Ok(HttpResponse::Created().json(ResourceServerResponse::from(&audience)))
+}
+
+/// Resource servers can be referenced by their identifier.
+/// See <https://auth0.com/docs/api/management/v2/resource-servers/get-resource-servers-by-id>
+#[get("/resource-servers/{id}")]
+pub async fn get_resource_server(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_RESOURCE_SERVERS)?;
+
+    let audience: Audience = find_resource_server(&app_data, &id)?;
+
+    Ok(HttpResponse::Ok().json(ResourceServerResponse::from(&audience)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/resource-servers/patch-resource-servers-by-id>
+#[patch("/resource-servers/{id}")]
+pub async fn update_resource_server(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<ResourceServerSettings>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_RESOURCE_SERVERS)?;
+
+    let mut audience: Audience = find_resource_server(&app_data, &id)?;
+    request.into_inner().apply(&mut audience)?;
+    check_signing_alg(&app_data, &audience)?;
+    app_data.audiences().put(audience.clone())?;
+
+    Ok(HttpResponse::Ok().json(ResourceServerResponse::from(&audience)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/resource-servers/delete-resource-servers-by-id>
+#[delete("/resource-servers/{id}")]
+pub async fn delete_resource_server(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, DELETE_RESOURCE_SERVERS)?;
+
+    app_data
+        .audiences()
+        .remove(&id)?
+        .ok_or_else(resource_server_not_found)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Keys are only generated on start, for the configured algorithms: tokens can't be signed with any other asymmetric one
+fn check_signing_alg(app_data: &AppData, audience: &Audience) -> Result<(), Error> {
+    let alg: Algorithm = audience.signing_alg();
+    if !is_symmetric_alg(alg) && app_data.jwks().signing_jwk_for(alg).is_err() {
+        return Err(Error::InvalidRequest(format!(
+            "No signing key available for the {alg:?} algorithm"
+        )));
+    }
+
+    Ok(())
+}
+
+fn find_resource_server(app_data: &AppData, id: &str) -> Result<Audience, Error> {
+    app_data.audiences().get(id)?.ok_or_else(resource_server_not_found)
+}
+
+fn resource_server_not_found() -> Error {
+    Error::NotFound("The resource server does not exist.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::{test, web::Data, App};
+    use serde_json::{json, Value};
+
+    use crate::config::Config;
+    use crate::controller::token;
+    use crate::management;
+    use crate::management::tests::token_request;
+    use crate::model::{AppData, TokenResponse};
+
+    #[actix_web::test]
+    async fn resource_servers_test() {
+        let config: Config = toml::from_str(
+            r#"
+            issuer = "https://localauth0.test/"
+
+            [[audience]]
+            name = "https://api.test"
+            display_name = "Test API"
+            permissions = ["read:items"]
+            token_lifetime = 600
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/resource-servers/https%3A%2F%2Fapi.test")
+            .insert_header(authorization.clone())
+            .to_request();
+        let resource_server: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resource_server,
+            json!({
+                "id": "https://api.test",
+                "name": "Test API",
+                "identifier": "https://api.test",
+                "scopes": [{ "value": "read:items" }],
+                "signing_alg": "RS256",
+                "token_lifetime": 600,
+                "enforce_policies": true,
+                "token_dialect": "access_token_authz"
+            })
+        );
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/resource-servers/https%3A%2F%2Fapi.test")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "token_dialect": "rfc9068_profile", "enforce_policies": false }))
+            .to_request();
+        let resource_server: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resource_server["token_dialect"], "rfc9068_profile");
+        assert_eq!(resource_server["enforce_policies"], false);
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://api.test").to_request()).await;
+        let header: jsonwebtoken::Header = jsonwebtoken::decode_header(resp.access_token()).unwrap();
+        assert_eq!(header.typ.as_deref(), Some("at+jwt"));
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/resource-servers")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "identifier": "https://api.test" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/resource-servers/https%3A%2F%2Fapi.test")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "token_lifetime": 0 }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        // Keys are generated on start only, for the configured algorithms
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/resource-servers/https%3A%2F%2Fapi.test")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "signing_alg": "PS512" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/resource-servers")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "identifier": "https://ps512.test", "signing_alg": "PS512" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/resource-servers")
+            .insert_header(authorization)
+            .set_json(json!({ "identifier": "https://hs256.test", "signing_alg": "HS256" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 201);
+    }
+
+    #[actix_web::test]
+    async fn resource_servers_sign_with_the_default_alg_test() {
+        let config: Config = toml::from_str(
+            r#"
+            issuer = "https://localauth0.test/"
+
+            [jwks]
+            signing_algs = ["ES256"]
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/resource-servers")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "identifier": "https://api.test" }))
+            .to_request();
+        let resource_server: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resource_server["signing_alg"], "ES256");
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://api.test").to_request()).await;
+        let header: jsonwebtoken::Header = jsonwebtoken::decode_header(resp.access_token()).unwrap();
+        assert_eq!(header.alg, jsonwebtoken::Algorithm::ES256);
+
+        // Configured algorithms only: the default one of the audiences isn't generated
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/resource-servers/https%3A%2F%2Fapi.test")
+            .insert_header(authorization)
+            .set_json(json!({ "signing_alg": "RS256" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+    }
+}
@@ -0,0 +1,346 @@
+use actix_web::web::{Data, Json, Path};
+use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse};
+
+use super::model::{CreateRoleRequest, Permission, PermissionsRequest, RoleResponse, UpdateRoleRequest};
+use super::{authorize, CREATE_ROLES, DELETE_ROLES, READ_ROLES, UPDATE_ROLES};
+use crate::error::Error;
+use crate::model::{AppData, Role};
+
+/// See <https://auth0.com/docs/api/management/v2/roles/get-roles>
+#[get("/roles")]
+pub async fn get_roles(app_data: Data<AppData>, req: HttpRequest) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_ROLES)?;
+
+    let roles: Vec<RoleResponse> = app_data.roles().all()?.iter().map(RoleResponse::from).collect();
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/roles/post-roles>
+#[post("/roles")]
+pub async fn create_role(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    request: Json<CreateRoleRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, CREATE_ROLES)?;
+
+    let CreateRoleRequest { name, description } = request.into_inner();
+
+    if app_data.roles().get(&name)?.is_some() {
+        return Err(Error::Conflict("Role already exists.".to_string()));
+    }
+
+    let role: Role = Role::new(name, description);
+    app_data.roles().put(role.clone())?;
+
+    Ok(HttpResponse::Ok().json(RoleResponse::from(&role)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/roles/get-roles-by-id>
+#[get("/roles/{id}")]
+pub async fn get_role(app_data: Data<AppData>, req: HttpRequest, id: Path<String>) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_ROLES)?;
+
+    let role: Role = find_role(&app_data, &id)?;
+
+    Ok(HttpResponse::Ok().json(RoleResponse::from(&role)))
+}
+
+/// Roles are identified by their name, so renaming a role changes its id.
+/// See <https://auth0.com/docs/api/management/v2/roles/patch-roles-by-id>
+#[patch("/roles/{id}")]
+pub async fn update_role(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<UpdateRoleRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_ROLES)?;
+
+    let mut role: Role = find_role(&app_data, &id)?;
+    let UpdateRoleRequest { name, description } = request.into_inner();
+
+    // Both `null` and an empty string clear the description
+    if let Some(description) = description {
+        role.set_description(description.filter(|description| !description.is_empty()));
+    }
+
+    match name {
+        Some(name) if name != role.name() => {
+            if app_data.roles().get(&name)?.is_some() {
+                return Err(Error::Conflict("Role already exists.".to_string()));
+            }
+
+            app_data.roles().remove(role.name())?;
+            app_data.users().rename_role(role.name(), &name)?;
+            role.set_name(name);
+        }
+        _ => (),
+    }
+
+    app_data.roles().put(role.clone())?;
+
+    Ok(HttpResponse::Ok().json(RoleResponse::from(&role)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/roles/delete-roles-by-id>
+#[delete("/roles/{id}")]
+pub async fn delete_role(app_data: Data<AppData>, req: HttpRequest, id: Path<String>) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, DELETE_ROLES)?;
+
+    app_data.roles().remove(&id)?.ok_or_else(role_not_found)?;
+    app_data.users().unassign_role(&id)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// See <https://auth0.com/docs/api/management/v2/roles/get-role-permission>
+#[get("/roles/{id}/permissions")]
+pub async fn get_role_permissions(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_ROLES)?;
+
+    let role: Role = find_role(&app_data, &id)?;
+
+    let mut permissions: Vec<Permission> = role
+        .permissions()
+        .iter()
+        .flat_map(|(audience, permissions)| {
+            permissions.iter().map(|permission| Permission {
+                resource_server_identifier: audience.to_string(),
+                permission_name: permission.to_string(),
+            })
+        })
+        .collect();
+    permissions.sort_by(|a, b| {
+        (&a.resource_server_identifier, &a.permission_name).cmp(&(&b.resource_server_identifier, &b.permission_name))
+    });
+
+    Ok(HttpResponse::Ok().json(permissions))
+}
+
+/// Permissions must be defined by the resource server.
+/// See <https://auth0.com/docs/api/management/v2/roles/post-role-permission-assignment>
+#[post("/roles/{id}/permissions")]
+pub async fn add_role_permissions(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<PermissionsRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_ROLES)?;
+
+    let mut role: Role = find_role(&app_data, &id)?;
+
+    for permission in request.into_inner().permissions {
+        let defined: Vec<String> = app_data
+            .audiences()
+            .get_permissions(&permission.resource_server_identifier)?;
+
+        if !defined.contains(&permission.permission_name) {
+            return Err(Error::InvalidRequest(format!(
+                "Permission '{}' does not exist in resource server '{}'",
+                permission.permission_name, permission.resource_server_identifier
+            )));
+        }
+
+        role.add_permission(&permission.resource_server_identifier, &permission.permission_name);
+    }
+
+    app_data.roles().put(role)?;
+
+    Ok(HttpResponse::Created().finish())
+}
+
+/// See <https://auth0.com/docs/api/management/v2/roles/delete-role-permission-assignment>
+#[delete("/roles/{id}/permissions")]
+pub async fn remove_role_permissions(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<PermissionsRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_ROLES)?;
+
+    let mut role: Role = find_role(&app_data, &id)?;
+
+    for permission in request.into_inner().permissions {
+        role.remove_permission(&permission.resource_server_identifier, &permission.permission_name);
+    }
+
+    app_data.roles().put(role)?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn find_role(app_data: &AppData, id: &str) -> Result<Role, Error> {
+    app_data.roles().get(id)?.ok_or_else(role_not_found)
+}
+
+fn role_not_found() -> Error {
+    Error::NotFound("The role does not exist.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{self, ContentType};
+    use actix_web::{test, web::Data, App};
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    use crate::config::Config;
+    use crate::controller::{login, token};
+    use crate::management;
+    use crate::management::tests::token_request;
+    use crate::model::{AppData, LoginResponse, TokenResponse};
+    use crate::CLIENT_ID_VALUE;
+
+    #[actix_web::test]
+    async fn roles_test() {
+        let config: Config = toml::from_str(
+            r#"
+            issuer = "https://localauth0.test/"
+
+            [[user]]
+            subject = "auth0|alice"
+            name = "Alice"
+            permissions = []
+            "#,
+        )
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let user_permissions = || async {
+            let req = test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_payload(json!({ "audience": "https://api.test", "user": "auth0|alice" }).to_string())
+                .to_request();
+            let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+
+            let req = test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_payload(
+                    json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code })
+                        .to_string(),
+                )
+                .to_request();
+            let resp: TokenResponse = test::call_and_read_body_json(&app, req).await;
+            let payload: &str = resp.access_token().split('.').nth(1).unwrap();
+            let access_token: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+            access_token["permissions"].clone()
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/resource-servers")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "identifier": "https://api.test", "name": "API", "scopes": [{ "value": "read:items" }, { "value": "write:items" }] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/roles")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "name": "editor", "description": "Edits items" }))
+            .to_request();
+        let role: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            role,
+            json!({ "id": "editor", "name": "editor", "description": "Edits items" })
+        );
+
+        let update_role = |body: Value| {
+            test::TestRequest::patch()
+                .uri("/api/v2/roles/editor")
+                .insert_header(authorization.clone())
+                .set_json(body)
+                .to_request()
+        };
+        let role: Value = test::call_and_read_body_json(&app, update_role(json!({ "name": "editor" }))).await;
+        assert_eq!(role["description"], "Edits items");
+        let role: Value = test::call_and_read_body_json(&app, update_role(json!({ "description": null }))).await;
+        assert_eq!(role, json!({ "id": "editor", "name": "editor" }));
+
+        let role: Value = test::call_and_read_body_json(&app, update_role(json!({ "description": "Edits" }))).await;
+        assert_eq!(role["description"], "Edits");
+        let role: Value = test::call_and_read_body_json(&app, update_role(json!({ "description": "" }))).await;
+        assert_eq!(role, json!({ "id": "editor", "name": "editor" }));
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/roles/editor/permissions")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "permissions": [{ "resource_server_identifier": "https://api.test", "permission_name": "delete:items" }] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/roles/editor/permissions")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "permissions": [{ "resource_server_identifier": "https://api.test", "permission_name": "write:items" }] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 201);
+
+        assert_eq!(user_permissions().await, json!([]));
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/users/auth0%7Calice/roles")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "roles": ["editor"] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+        assert_eq!(user_permissions().await, json!(["write:items"]));
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users/auth0%7Calice/roles")
+            .insert_header(authorization.clone())
+            .to_request();
+        let roles: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(roles[0]["id"], "editor");
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v2/roles/editor/permissions")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "permissions": [{ "resource_server_identifier": "https://api.test", "permission_name": "write:items" }] }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/roles/editor/permissions")
+            .insert_header(authorization.clone())
+            .to_request();
+        let permissions: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(permissions, json!([]));
+        assert_eq!(user_permissions().await, json!([]));
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v2/roles/editor")
+            .insert_header(authorization.clone())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users/auth0%7Calice/roles")
+            .insert_header(authorization)
+            .to_request();
+        let roles: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(roles, json!([]));
+    }
+}
@@ -0,0 +1,491 @@
+use actix_web::web::{Data, Json, Path, Query};
+use actix_web::{delete, get, patch, post, HttpRequest, HttpResponse};
+use chrono::Utc;
+
+use super::model::{
+    merge_metadata, CreateUserRequest, ListUsersQuery, RoleResponse, RolesRequest, UpdateUserRequest, UserResponse,
+    UsersByEmailQuery, UsersPage,
+};
+use super::{authorize, CREATE_USERS, DELETE_USERS, READ_USERS, UPDATE_USERS};
+use crate::error::Error;
+use crate::model::{defaults, AppData, User, UserInfo};
+
+const DEFAULT_PER_PAGE: usize = 50;
+const MAX_PER_PAGE: usize = 100;
+
+/// See <https://auth0.com/docs/api/management/v2/users/get-users>
+#[get("/users")]
+pub async fn get_users(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    query: Query<ListUsersQuery>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_USERS)?;
+
+    let ListUsersQuery {
+        page,
+        per_page,
+        include_totals,
+    } = query.into_inner();
+    let per_page: usize = per_page.unwrap_or(DEFAULT_PER_PAGE).min(MAX_PER_PAGE);
+    let start: usize = page
+        .checked_mul(per_page)
+        .ok_or_else(|| Error::InvalidRequest(format!("Page {page} is out of range")))?;
+
+    let users: Vec<User> = app_data.users().all()?;
+    let total: usize = users.len();
+    let users: Vec<UserResponse> = users
+        .iter()
+        .skip(start)
+        .take(per_page)
+        .map(UserResponse::from)
+        .collect();
+
+    if include_totals {
+        Ok(HttpResponse::Ok().json(UsersPage {
+            start,
+            limit: per_page,
+            length: users.len(),
+            total,
+            users,
+        }))
+    } else {
+        Ok(HttpResponse::Ok().json(users))
+    }
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users/post-users>
+#[post("/users")]
+pub async fn create_user(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    request: Json<CreateUserRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, CREATE_USERS)?;
+
+    let request: CreateUserRequest = request.into_inner();
+    let id: String = request
+        .user_id
+        .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+    let nickname: String = request
+        .email
+        .split_once('@')
+        .map_or(request.email.as_str(), |(local_part, _)| local_part)
+        .to_string();
+
+    let mut user: User = User::new(UserInfo {
+        sub: format!("auth0|{id}"),
+        name: request.name.unwrap_or_else(|| request.email.clone()),
+        given_name: request.given_name.unwrap_or_default(),
+        family_name: request.family_name.unwrap_or_default(),
+        nickname: request.nickname.unwrap_or(nickname),
+        locale: String::new(),
+        gender: String::new(),
+        birthdate: String::new(),
+        email: request.email,
+        email_verified: request.email_verified.unwrap_or_default(),
+        picture: request.picture.unwrap_or_else(defaults::user_info_picture),
+        updated_at: Utc::now(),
+        custom_fields: None,
+    });
+    user.set_blocked(request.blocked.unwrap_or_default());
+    *user.app_metadata_mut() = request.app_metadata.unwrap_or_default();
+    *user.user_metadata_mut() = request.user_metadata.unwrap_or_default();
+
+    app_data.users().create(user.clone())?;
+
+    Ok(HttpResponse::Created().json(UserResponse::from(&user)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users/get-users-by-id>
+#[get("/users/{id}")]
+pub async fn get_user(app_data: Data<AppData>, req: HttpRequest, id: Path<String>) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_USERS)?;
+
+    let user: User = find_user(&app_data, &id)?;
+
+    Ok(HttpResponse::Ok().json(UserResponse::from(&user)))
+}
+
+/// Properties are replaced, while metadata are merged. See
+/// <https://auth0.com/docs/api/management/v2/users/patch-users-by-id>
+#[patch("/users/{id}")]
+pub async fn update_user(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<UpdateUserRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_USERS)?;
+
+    let mut user: User = find_user(&app_data, &id)?;
+    let request: UpdateUserRequest = request.into_inner();
+
+    let user_info: &mut UserInfo = user.user_info_mut();
+    user_info.email = request.email.unwrap_or(user_info.email.clone());
+    user_info.email_verified = request.email_verified.unwrap_or(user_info.email_verified);
+    user_info.name = request.name.unwrap_or(user_info.name.clone());
+    user_info.given_name = request.given_name.unwrap_or(user_info.given_name.clone());
+    user_info.family_name = request.family_name.unwrap_or(user_info.family_name.clone());
+    user_info.nickname = request.nickname.unwrap_or(user_info.nickname.clone());
+    user_info.picture = request.picture.unwrap_or(user_info.picture.clone());
+    user_info.updated_at = Utc::now();
+
+    if let Some(blocked) = request.blocked {
+        user.set_blocked(blocked);
+
+        // Blocked users can't keep refreshing their tokens
+        if blocked {
+            app_data.refresh_tokens().revoke_for_subject(user.subject())?;
+        }
+    }
+
+    if let Some(app_metadata) = request.app_metadata {
+        merge_metadata(user.app_metadata_mut(), app_metadata);
+    }
+
+    if let Some(user_metadata) = request.user_metadata {
+        merge_metadata(user.user_metadata_mut(), user_metadata);
+    }
+
+    app_data.users().put(user.clone())?;
+
+    Ok(HttpResponse::Ok().json(UserResponse::from(&user)))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users/delete-users-by-id>
+#[delete("/users/{id}")]
+pub async fn delete_user(app_data: Data<AppData>, req: HttpRequest, id: Path<String>) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, DELETE_USERS)?;
+
+    app_data.users().remove(&id)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users-by-email/get-users-by-email>
+#[get("/users-by-email")]
+pub async fn get_users_by_email(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    query: Query<UsersByEmailQuery>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_USERS)?;
+
+    let users: Vec<UserResponse> = app_data
+        .users()
+        .find_by_email(&query.email)?
+        .iter()
+        .map(UserResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(users))
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users/get-user-roles>
+#[get("/users/{id}/roles")]
+pub async fn get_user_roles(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, READ_USERS)?;
+
+    let user: User = find_user(&app_data, &id)?;
+    let roles: Vec<RoleResponse> = app_data
+        .roles()
+        .get_all(user.roles())?
+        .iter()
+        .map(RoleResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(roles))
+}
+
+/// Assigned roles change the permissions of the tokens issued to the user from then on.
+/// See <https://auth0.com/docs/api/management/v2/users/post-user-roles>
+#[post("/users/{id}/roles")]
+pub async fn assign_user_roles(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<RolesRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_USERS)?;
+
+    find_user(&app_data, &id)?;
+
+    let roles: Vec<String> = request.into_inner().roles;
+    if app_data.roles().find_unknown(&roles)?.is_some() {
+        return Err(Error::NotFound("The role does not exist.".to_string()));
+    }
+
+    app_data.users().assign_roles(&id, roles)?.ok_or_else(user_not_found)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// See <https://auth0.com/docs/api/management/v2/users/delete-user-roles>
+#[delete("/users/{id}/roles")]
+pub async fn remove_user_roles(
+    app_data: Data<AppData>,
+    req: HttpRequest,
+    id: Path<String>,
+    request: Json<RolesRequest>,
+) -> Result<HttpResponse, Error> {
+    authorize(&app_data, &req, UPDATE_USERS)?;
+
+    app_data
+        .users()
+        .remove_roles(&id, &request.into_inner().roles)?
+        .ok_or_else(user_not_found)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn find_user(app_data: &AppData, id: &str) -> Result<User, Error> {
+    app_data.users().get(id)?.ok_or_else(user_not_found)
+}
+
+fn user_not_found() -> Error {
+    Error::NotFound("The user does not exist.".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header;
+    use actix_web::{test, web::Data, App};
+    use serde_json::{json, Value};
+
+    use crate::config::Config;
+    use crate::controller::token;
+    use crate::management;
+    use crate::management::tests::token_request;
+    use crate::model::{AppData, TokenResponse};
+
+    const CONFIG: &str = r#"
+    issuer = "https://localauth0.test/"
+
+    [[user]]
+    subject = "auth0|alice"
+    name = "Alice"
+    email = "Alice@example.com"
+    app_metadata = { plan = "free", features = { beta = true } }
+    "#;
+
+    #[actix_web::test]
+    async fn users_test() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users/auth0%7Calice")
+            .insert_header(authorization.clone())
+            .to_request();
+        let user: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(user["user_id"], "auth0|alice");
+        assert_eq!(user["identities"][0]["user_id"], "alice");
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/users/auth0%7Calice")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "name": "Alice Liddell", "app_metadata": { "plan": "pro", "features": null } }))
+            .to_request();
+        let user: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(user["name"], "Alice Liddell");
+        assert_eq!(user["app_metadata"], json!({ "plan": "pro" }));
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/users")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "user_id": "bob", "email": "bob@example.com", "connection": "Username-Password-Authentication" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 201);
+        let user: Value = test::read_body_json(resp).await;
+        assert_eq!(user["user_id"], "auth0|bob");
+        assert_eq!(user["nickname"], "bob");
+
+        let req = test::TestRequest::post()
+            .uri("/api/v2/users")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "user_id": "bob", "email": "bob@example.com" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 409);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users?include_totals=true&per_page=1")
+            .insert_header(authorization.clone())
+            .to_request();
+        let page: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(page["total"], 2);
+        assert_eq!(page["users"].as_array().unwrap().len(), 1);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/v2/users?page={}&per_page=100", usize::MAX))
+            .insert_header(authorization.clone())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 400);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users-by-email?email=alice%40example.com")
+            .insert_header(authorization.clone())
+            .to_request();
+        let users: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(users[0]["user_id"], "auth0|alice");
+
+        let req = test::TestRequest::delete()
+            .uri("/api/v2/users/auth0%7Cbob")
+            .insert_header(authorization.clone())
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 204);
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users/auth0%7Cbob")
+            .insert_header(authorization)
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 404);
+    }
+
+    #[actix_web::test]
+    async fn users_authorization_test() {
+        let config: Config = toml::from_str(&format!(
+            r#"{CONFIG}
+            [[audience]]
+            name = "https://localauth0.test/api/v2/"
+            permissions = ["read:users"]
+            "#
+        ))
+        .unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/v2/users").to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+        // Tokens for other audiences are rejected
+        let resp: TokenResponse = test::call_and_read_body_json(&app, token_request("audience").to_request()).await;
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users")
+            .insert_header((header::AUTHORIZATION, format!("Bearer {}", resp.access_token())))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), 401);
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let req = test::TestRequest::get()
+            .uri("/api/v2/users")
+            .insert_header(authorization.clone())
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/users/auth0%7Calice")
+            .insert_header(authorization)
+            .set_json(json!({ "name": "Alice Liddell" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), 403);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "insufficient_scope");
+    }
+
+    #[actix_web::test]
+    async fn blocked_users_test() {
+        use actix_web::http::header::ContentType;
+
+        use crate::controller::login;
+        use crate::model::LoginResponse;
+        use crate::CLIENT_ID_VALUE;
+
+        let config: Config = toml::from_str(CONFIG).unwrap();
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(AppData::new(&config).unwrap()))
+                .service(login)
+                .service(token)
+                .configure(management::configure),
+        )
+        .await;
+
+        let resp: TokenResponse =
+            test::call_and_read_body_json(&app, token_request("https://localauth0.test/api/v2/").to_request()).await;
+        let authorization = (header::AUTHORIZATION, format!("Bearer {}", resp.access_token()));
+
+        let log_in = || async {
+            let req = test::TestRequest::post()
+                .uri("/oauth/login")
+                .insert_header(ContentType::json())
+                .set_json(json!({ "audience": "audience1", "user": "auth0|alice", "scope": "openid offline_access" }))
+                .to_request();
+            let LoginResponse { code } = test::call_and_read_body_json(&app, req).await;
+            code
+        };
+        let exchange = |body: Value| {
+            test::TestRequest::post()
+                .uri("/oauth/token")
+                .insert_header(ContentType::json())
+                .set_json(body)
+                .to_request()
+        };
+
+        let code: String = log_in().await;
+        let resp: TokenResponse = test::call_and_read_body_json(
+            &app,
+            exchange(json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": code })),
+        )
+        .await;
+        let refresh_token: String = resp.refresh_token().unwrap().to_string();
+        let pending_code: String = log_in().await;
+
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/users/auth0%7Calice")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "blocked": true }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let resp = test::call_service(
+            &app,
+            exchange(json!({ "grant_type": "authorization_code", "client_id": CLIENT_ID_VALUE, "code": pending_code })),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+
+        // Refresh tokens are revoked, so they stay invalid once the user is unblocked
+        let req = test::TestRequest::patch()
+            .uri("/api/v2/users/auth0%7Calice")
+            .insert_header(authorization.clone())
+            .set_json(json!({ "blocked": false }))
+            .to_request();
+        assert!(test::call_service(&app, req).await.status().is_success());
+
+        let resp = test::call_service(
+            &app,
+            exchange(
+                json!({ "grant_type": "refresh_token", "client_id": CLIENT_ID_VALUE, "refresh_token": refresh_token }),
+            ),
+        )
+        .await;
+        assert_eq!(resp.status(), 400);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "invalid_grant");
+    }
+}
@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::config::{ActionConfig, CustomField, CustomFieldValue};
+use crate::error::Error;
+use crate::model::GrantType;
+
+/// Claims set by localauth0 itself, which can't be changed by actions
+const RESERVED_CLAIMS: [&str; 15] = [
+    "iss",
+    "sub",
+    "aud",
+    "azp",
+    "exp",
+    "nbf",
+    "iat",
+    "jti",
+    "scope",
+    "gty",
+    "permissions",
+    "client_id",
+    "sid",
+    "nonce",
+    "auth_time",
+];
+
+/// Profile claims of the id token, which come from the user info
+const PROFILE_CLAIMS: [&str; 11] = [
+    "name",
+    "given_name",
+    "family_name",
+    "nickname",
+    "locale",
+    "gender",
+    "birthdate",
+    "email",
+    "email_verified",
+    "picture",
+    "updated_at",
+];
+
+/// Script run while issuing tokens. The `event` variable describes the token being issued and the `api` one
+/// allows to change it, e.g. `api.accessToken.setCustomClaim("https://example.com/tenant", "acme")` or
+/// `api.access.deny("reason")`. See <https://auth0.com/docs/customize/actions>
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Action {
+    name: String,
+    trigger: Trigger,
+    script: String,
+}
+
+impl Action {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn trigger(&self) -> Trigger {
+        self.trigger
+    }
+
+    pub fn script(&self) -> &str {
+        &self.script
+    }
+}
+
+impl From<&ActionConfig> for Action {
+    fn from(value: &ActionConfig) -> Self {
+        Self {
+            name: value.name().to_string(),
+            trigger: *value.trigger(),
+            script: value.script().to_string(),
+        }
+    }
+}
+
+/// See <https://auth0.com/docs/customize/actions/explore-triggers>
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    /// Authorization code and refresh token grants
+    PostLogin,
+    /// Client credentials grant
+    CredentialsExchange,
+}
+
+impl From<&GrantType> for Trigger {
+    fn from(value: &GrantType) -> Self {
+        match value {
+            GrantType::ClientCredentials => Trigger::CredentialsExchange,
+            GrantType::AuthorizationCode | GrantType::RefreshToken => Trigger::PostLogin,
+        }
+    }
+}
+
+/// Changes to the custom claims of the tokens being issued. A `null` claim is removed.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct ClaimsPatch {
+    #[serde(default)]
+    pub access_token: Map<String, Value>,
+    #[serde(default)]
+    pub id_token: Map<String, Value>,
+}
+
+impl ClaimsPatch {
+    /// Later changes to the same claim win
+    pub fn merge(&mut self, other: ClaimsPatch) {
+        self.access_token.extend(other.access_token);
+        self.id_token.extend(other.id_token);
+    }
+
+    /// Changing a reserved claim fails with the given error, telling whether an action or a webhook is to blame
+    pub fn apply_to_access_token(
+        &self,
+        custom_claims: &mut Vec<CustomField>,
+        error: fn(String) -> Error,
+    ) -> Result<(), Error> {
+        apply(&self.access_token, custom_claims, &[], error)
+    }
+
+    pub fn apply_to_id_token(
+        &self,
+        custom_fields: &mut Vec<CustomField>,
+        error: fn(String) -> Error,
+    ) -> Result<(), Error> {
+        apply(&self.id_token, custom_fields, &PROFILE_CLAIMS, error)
+    }
+}
+
+fn apply(
+    patch: &Map<String, Value>,
+    custom_fields: &mut Vec<CustomField>,
+    reserved: &[&str],
+    error: fn(String) -> Error,
+) -> Result<(), Error> {
+    for (name, value) in patch {
+        if RESERVED_CLAIMS.contains(&name.as_str()) || reserved.contains(&name.as_str()) {
+            return Err(error(format!("Claim '{name}' can't be changed")));
+        }
+
+        custom_fields.retain(|custom_field| custom_field.name() != name);
+        if !value.is_null() {
+            custom_fields.push(CustomField::new(
+                name.to_string(),
+                CustomFieldValue::Json(value.clone()),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::ClaimsPatch;
+    use crate::config::{CustomField, CustomFieldValue};
+    use crate::error::Error;
+
+    #[test]
+    fn claims_patch_test() {
+        let mut custom_claims: Vec<CustomField> = vec![
+            CustomField::new("kept".to_string(), CustomFieldValue::Bool(true)),
+            CustomField::new("removed".to_string(), CustomFieldValue::Bool(true)),
+            CustomField::new("replaced".to_string(), CustomFieldValue::Bool(true)),
+        ];
+
+        let patch: ClaimsPatch = serde_json::from_value(json!({
+            "access_token": { "removed": null, "replaced": { "tenant": "acme" }, "added": 1 }
+        }))
+        .unwrap();
+        patch
+            .apply_to_access_token(&mut custom_claims, Error::ActionFailed)
+            .unwrap();
+
+        let claims: Vec<(&str, serde_json::Value)> = custom_claims
+            .iter()
+            .map(|claim| (claim.name().as_str(), claim.value().to_json()))
+            .collect();
+        assert_eq!(
+            claims,
+            vec![
+                ("kept", json!(true)),
+                ("added", json!(1)),
+                ("replaced", json!({ "tenant": "acme" }))
+            ]
+        );
+
+        let patch: ClaimsPatch = serde_json::from_value(json!({ "access_token": { "sub": "someone" } })).unwrap();
+        assert!(matches!(
+            patch.apply_to_access_token(&mut custom_claims, Error::ActionFailed),
+            Err(Error::ActionFailed(_))
+        ));
+        assert!(matches!(
+            patch.apply_to_access_token(&mut custom_claims, Error::WebhookFailed),
+            Err(Error::WebhookFailed(_))
+        ));
+
+        let patch: ClaimsPatch = serde_json::from_value(json!({ "access_token": { "email": "a@b.c" } })).unwrap();
+        assert!(patch
+            .apply_to_access_token(&mut custom_claims, Error::ActionFailed)
+            .is_ok());
+        let patch: ClaimsPatch = serde_json::from_value(json!({ "id_token": { "email": "a@b.c" } })).unwrap();
+        assert!(patch
+            .apply_to_id_token(&mut custom_claims, Error::ActionFailed)
+            .is_err());
+    }
+}
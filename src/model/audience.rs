@@ -0,0 +1,203 @@
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::config::AudienceConfig;
+use crate::model::{is_symmetric_alg, Webhook};
+
+/// An audience is an API, Auth0 resource server, whose settings drive the access tokens issued for it.
+/// See <https://auth0.com/docs/get-started/apis/api-settings>
+#[derive(Debug, Clone)]
+pub struct Audience {
+    identifier: String,
+    name: Option<String>,
+    permissions: Vec<String>,
+    token_lifetime: Option<i64>,
+    signing_alg: Algorithm,
+    /// Shared secret of the HS algorithms, never published in the jwks
+    signing_secret: Option<String>,
+    enable_rbac: bool,
+    add_permissions_in_token: bool,
+    token_dialect: TokenDialect,
+    webhook: Option<Webhook>,
+}
+
+impl Audience {
+    /// Tokens are signed with the given algorithm, usually the default one of the jwks
+    pub fn new(identifier: String, permissions: Vec<String>, signing_alg: Algorithm) -> Self {
+        Self {
+            identifier,
+            name: None,
+            permissions,
+            token_lifetime: None,
+            signing_alg,
+            signing_secret: None,
+            enable_rbac: true,
+            add_permissions_in_token: true,
+            token_dialect: TokenDialect::default(),
+            webhook: None,
+        }
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    /// Display name, defaults to the identifier
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.identifier)
+    }
+
+    pub fn set_name(&mut self, name: Option<String>) {
+        self.name = name;
+    }
+
+    pub fn permissions(&self) -> &Vec<String> {
+        &self.permissions
+    }
+
+    pub fn set_permissions(&mut self, permissions: Vec<String>) {
+        self.permissions = permissions;
+    }
+
+    /// Lifetime in seconds of the access tokens, the default one when missing
+    pub fn token_lifetime(&self) -> Option<i64> {
+        self.token_lifetime
+    }
+
+    pub fn set_token_lifetime(&mut self, token_lifetime: Option<i64>) {
+        self.token_lifetime = token_lifetime;
+    }
+
+    pub fn signing_alg(&self) -> Algorithm {
+        self.signing_alg
+    }
+
+    /// Switching to an HS algorithm generates the signing secret, unless one is already set
+    pub fn set_signing_alg(&mut self, signing_alg: Algorithm) {
+        self.signing_alg = signing_alg;
+        if is_symmetric_alg(signing_alg) && self.signing_secret.is_none() {
+            self.signing_secret = Some(generate_signing_secret());
+        }
+    }
+
+    pub fn signing_secret(&self) -> Option<&str> {
+        self.signing_secret.as_deref()
+    }
+
+    pub fn set_signing_secret(&mut self, signing_secret: Option<String>) {
+        self.signing_secret = signing_secret;
+    }
+
+    /// When disabled users are granted every permission of the audience, whatever their roles
+    pub fn enable_rbac(&self) -> bool {
+        self.enable_rbac
+    }
+
+    pub fn set_enable_rbac(&mut self, enable_rbac: bool) {
+        self.enable_rbac = enable_rbac;
+    }
+
+    pub fn add_permissions_in_token(&self) -> bool {
+        self.add_permissions_in_token
+    }
+
+    pub fn set_add_permissions_in_token(&mut self, add_permissions_in_token: bool) {
+        self.add_permissions_in_token = add_permissions_in_token;
+    }
+
+    pub fn token_dialect(&self) -> TokenDialect {
+        self.token_dialect
+    }
+
+    pub fn set_token_dialect(&mut self, token_dialect: TokenDialect) {
+        self.token_dialect = token_dialect;
+    }
+
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
+
+    /// Whether the `permissions` claim is added to the access tokens
+    pub fn includes_permissions(&self) -> bool {
+        self.add_permissions_in_token || self.token_dialect.is_authz()
+    }
+}
+
+impl From<&AudienceConfig> for Audience {
+    fn from(value: &AudienceConfig) -> Self {
+        let signing_secret: Option<String> = match value.signing_secret() {
+            Some(signing_secret) => Some(signing_secret.to_string()),
+            None if is_symmetric_alg(*value.signing_alg()) => Some(generate_signing_secret()),
+            None => None,
+        };
+
+        Self {
+            identifier: value.name().to_string(),
+            name: value.display_name().clone(),
+            permissions: value.permissions().clone(),
+            token_lifetime: *value.token_lifetime(),
+            signing_alg: *value.signing_alg(),
+            signing_secret,
+            enable_rbac: *value.enable_rbac(),
+            add_permissions_in_token: *value.add_permissions_in_token(),
+            token_dialect: *value.token_dialect(),
+            webhook: value.webhook().clone(),
+        }
+    }
+}
+
+/// 256 random bits, base64url encoded
+fn generate_signing_secret() -> String {
+    base64_url::encode(&rand::random::<[u8; 32]>())
+}
+
+/// Profile of the access tokens.
+/// See <https://auth0.com/docs/secure/tokens/access-tokens/access-token-profiles>
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenDialect {
+    #[default]
+    AccessToken,
+    AccessTokenAuthz,
+    Rfc9068Profile,
+    Rfc9068ProfileAuthz,
+}
+
+impl TokenDialect {
+    /// `_authz` dialects always carry the `permissions` claim
+    pub fn is_authz(&self) -> bool {
+        matches!(self, TokenDialect::AccessTokenAuthz | TokenDialect::Rfc9068ProfileAuthz)
+    }
+
+    /// See <https://datatracker.ietf.org/doc/html/rfc9068>
+    pub fn is_rfc9068(&self) -> bool {
+        matches!(self, TokenDialect::Rfc9068Profile | TokenDialect::Rfc9068ProfileAuthz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenDialect;
+
+    #[test]
+    fn token_dialect_is_deserialized_from_auth0_names_test() {
+        let dialects: Vec<TokenDialect> = serde_json::from_value(serde_json::json!([
+            "access_token",
+            "access_token_authz",
+            "rfc9068_profile",
+            "rfc9068_profile_authz"
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            dialects,
+            vec![
+                TokenDialect::AccessToken,
+                TokenDialect::AccessTokenAuthz,
+                TokenDialect::Rfc9068Profile,
+                TokenDialect::Rfc9068ProfileAuthz
+            ]
+        );
+        assert!(dialects.iter().filter(|dialect| dialect.is_authz()).count() == 2);
+    }
+}
@@ -1,7 +1,10 @@
+use jsonwebtoken::Algorithm;
 use openssl::asn1::Asn1Time;
 use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
 use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
 use openssl::rsa::Rsa;
 use openssl::x509::extension::{BasicConstraints, KeyUsage, SubjectKeyIdentifier};
 use openssl::x509::{X509NameBuilder, X509};
@@ -14,6 +17,28 @@ pub fn generate_private_key() -> Result<PKey<Private>, Error> {
     Ok(pkey)
 }
 
+/// Key pair signing with the given algorithm: RSA-2048 for the RS and PS algorithms, P-256 and P-384 curves for
+/// the ES ones and Ed25519 for EdDSA
+pub fn generate_signing_key(alg: Algorithm) -> Result<PKey<Private>, Error> {
+    match alg {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => generate_private_key(),
+        Algorithm::ES256 => generate_ec_key(Nid::X9_62_PRIME256V1),
+        Algorithm::ES384 => generate_ec_key(Nid::SECP384R1),
+        Algorithm::EdDSA => Ok(PKey::generate_ed25519()?),
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512 => Err(Error::UnsupportedSigningAlg(alg)),
+    }
+}
+
+fn generate_ec_key(curve: Nid) -> Result<PKey<Private>, Error> {
+    let group = EcGroup::from_curve_name(curve)?;
+    Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+}
+
 pub fn generate_certificate(key_pair: &PKey<Private>) -> Result<X509, Error> {
     let mut x509_name = X509NameBuilder::new()?;
     x509_name.append_entry_by_text("C", "US")?;
@@ -50,7 +75,13 @@ pub fn generate_certificate(key_pair: &PKey<Private>) -> Result<X509, Error> {
     let subject_key_identifier = SubjectKeyIdentifier::new().build(&cert_builder.x509v3_context(None, None))?;
     cert_builder.append_extension(subject_key_identifier)?;
 
-    cert_builder.sign(key_pair, MessageDigest::sha256())?;
+    // Ed25519 signatures embed their own digest
+    let digest = if key_pair.id() == Id::ED25519 {
+        MessageDigest::null()
+    } else {
+        MessageDigest::sha256()
+    };
+    cert_builder.sign(key_pair, digest)?;
     let cert = cert_builder.build();
 
     Ok(cert)
@@ -3,20 +3,36 @@ use std::fmt::{Display, Formatter};
 use serde::{ser::SerializeMap, Deserialize, Serialize, Serializer};
 use uuid::Uuid;
 
-use crate::config::{CustomField, CustomFieldValue};
+use crate::config::CustomField;
+use crate::model::defaults;
+
+/// Longest lifetime in seconds a token can be issued with, ten years
+pub const MAX_TOKEN_LIFETIME: i64 = 315_360_000;
+
+/// Whether tokens can be issued with the given lifetime in seconds, which mustn't overflow their `exp`
+pub fn is_valid_lifetime(lifetime: i64) -> bool {
+    (1..=MAX_TOKEN_LIFETIME).contains(&lifetime)
+}
 
 #[derive(Debug, Deserialize)]
 pub struct Claims {
     iss: String,
     sub: String,
     aud: String,
+    azp: String,
     exp: Option<i64>,
     nbf: Option<i64>,
     iat: Option<i64>,
     jti: String,
     scope: String,
     gty: GrantType,
+    #[serde(default)]
     permissions: Vec<String>,
+    /// Only set by the RFC 9068 token dialects
+    #[serde(default)]
+    client_id: Option<String>,
+    #[serde(skip_deserializing)]
+    permissions_claim: bool,
     // skip deserializing since deserialization from a jwt wouldn't match this struct
     // a custom deserializer would be needed
     #[serde(skip_deserializing)]
@@ -26,6 +42,7 @@ pub struct Claims {
 impl Claims {
     pub fn new(
         aud: String,
+        azp: String,
         permissions: Vec<String>,
         iss: String,
         sub: String,
@@ -36,17 +53,48 @@ impl Claims {
             iss,
             sub,
             aud,
-            exp: Some(chrono::Utc::now().timestamp() + 60000),
+            azp,
+            exp: Some(chrono::Utc::now().timestamp() + defaults::access_token_lifetime()),
             nbf: Some(chrono::Utc::now().timestamp()),
             iat: Some(chrono::Utc::now().timestamp()),
             jti: Uuid::new_v4().to_string(),
             scope: permissions.join(" "),
             gty,
             permissions,
+            client_id: None,
+            permissions_claim: true,
             custom_claims,
         }
     }
 
+    pub fn with_scope(self, scope: String) -> Self {
+        Self { scope, ..self }
+    }
+
+    /// Token lifetime in seconds, starting from the issue time
+    pub fn with_lifetime(self, lifetime: i64) -> Self {
+        Self {
+            exp: self.iat.map(|iat| iat + lifetime),
+            ..self
+        }
+    }
+
+    /// Whether the `permissions` claim is serialized. Permissions are granted through the scope anyway
+    pub fn with_permissions_claim(self, permissions_claim: bool) -> Self {
+        Self {
+            permissions_claim,
+            ..self
+        }
+    }
+
+    /// Adds the `client_id` claim required by <https://datatracker.ietf.org/doc/html/rfc9068#section-2.2>
+    pub fn with_client_id(self) -> Self {
+        Self {
+            client_id: Some(self.azp.clone()),
+            ..self
+        }
+    }
+
     pub fn has_permission(&self, permission: &str) -> bool {
         self.permissions.iter().any(|x| x == permission)
     }
@@ -55,6 +103,10 @@ impl Claims {
         &self.aud
     }
 
+    pub fn authorized_party(&self) -> &str {
+        &self.azp
+    }
+
     pub fn issuer(&self) -> &str {
         &self.iss
     }
@@ -63,10 +115,22 @@ impl Claims {
         &self.sub
     }
 
+    pub fn scope(&self) -> &str {
+        &self.scope
+    }
+
+    pub fn expiration(&self) -> Option<i64> {
+        self.exp
+    }
+
     pub fn grant_type(&self) -> &GrantType {
         &self.gty
     }
 
+    pub fn custom_claims_mut(&mut self) -> &mut Vec<CustomField> {
+        &mut self.custom_claims
+    }
+
     #[cfg(test)]
     pub fn custom_claims(&self) -> &Vec<CustomField> {
         &self.custom_claims
@@ -83,30 +147,34 @@ impl Serialize for Claims {
         map.serialize_entry("iss", &self.iss)?;
         map.serialize_entry("sub", &self.sub)?;
         map.serialize_entry("aud", &self.aud)?;
+        map.serialize_entry("azp", &self.azp)?;
+        if let Some(client_id) = &self.client_id {
+            map.serialize_entry("client_id", client_id)?;
+        }
         map.serialize_entry("exp", &self.exp)?;
         map.serialize_entry("nbf", &self.nbf)?;
         map.serialize_entry("iat", &self.iat)?;
         map.serialize_entry("jti", &self.jti)?;
         map.serialize_entry("scope", &self.scope)?;
         map.serialize_entry("gty", &self.gty)?;
-        map.serialize_entry("permissions", &self.permissions)?;
+        if self.permissions_claim {
+            map.serialize_entry("permissions", &self.permissions)?;
+        }
 
         for custom_claims in &self.custom_claims {
-            match custom_claims.value() {
-                CustomFieldValue::String(string) => map.serialize_entry(custom_claims.name(), &string),
-                CustomFieldValue::Vec(vec) => map.serialize_entry(custom_claims.name(), &vec),
-            }?;
+            map.serialize_entry(custom_claims.name(), &custom_claims.value().to_json())?;
         }
 
         map.end()
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum GrantType {
     ClientCredentials,
     AuthorizationCode,
+    RefreshToken,
 }
 
 impl Display for GrantType {
@@ -114,6 +182,7 @@ impl Display for GrantType {
         match self {
             GrantType::ClientCredentials => write!(f, "client_credentials"),
             GrantType::AuthorizationCode => write!(f, "authorization_code"),
+            GrantType::RefreshToken => write!(f, "refresh_token"),
         }
     }
 }
@@ -0,0 +1,74 @@
+use crate::config::ClientConfig;
+use crate::model::{GrantType, Webhook};
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    id: String,
+    secret: String,
+    name: String,
+    public: bool,
+    grant_types: Vec<GrantType>,
+    callback_urls: Vec<String>,
+    audiences: Vec<String>,
+    id_token_lifetime: Option<i64>,
+    webhook: Option<Webhook>,
+}
+
+impl Client {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn id_token_lifetime(&self) -> Option<i64> {
+        self.id_token_lifetime
+    }
+
+    pub fn webhook(&self) -> Option<&Webhook> {
+        self.webhook.as_ref()
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.public
+    }
+
+    pub fn verify_secret(&self, secret: &str) -> bool {
+        self.secret == secret
+    }
+
+    /// A missing secret only authenticates public clients
+    pub fn authenticate(&self, secret: Option<&str>) -> bool {
+        secret.map_or(self.public, |secret| self.verify_secret(secret))
+    }
+
+    pub fn allows_grant_type(&self, grant_type: &GrantType) -> bool {
+        self.grant_types.is_empty() || self.grant_types.contains(grant_type)
+    }
+
+    pub fn allows_callback_url(&self, callback_url: &str) -> bool {
+        self.callback_urls.is_empty() || self.callback_urls.iter().any(|url| url == callback_url)
+    }
+
+    pub fn allows_audience(&self, audience: &str) -> bool {
+        self.audiences.is_empty() || self.audiences.iter().any(|aud| aud == audience)
+    }
+}
+
+impl From<&ClientConfig> for Client {
+    fn from(value: &ClientConfig) -> Self {
+        Self {
+            id: value.id().to_string(),
+            secret: value.secret().to_string(),
+            name: value.name().clone().unwrap_or_else(|| value.id().to_string()),
+            public: *value.public(),
+            grant_types: value.grant_types().clone(),
+            callback_urls: value.callback_urls().clone(),
+            audiences: value.audiences().clone(),
+            id_token_lifetime: *value.id_token_lifetime(),
+            webhook: value.webhook().clone(),
+        }
+    }
+}
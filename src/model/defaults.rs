@@ -1,5 +1,7 @@
 use crate::model::{Issuer, Subject};
+use crate::{CLIENT_ID_VALUE, CLIENT_SECRET_VALUE};
 use chrono::{DateTime, Utc};
+use jsonwebtoken::Algorithm;
 
 const ISSUER: &str = "https://prima.localauth0.com/";
 
@@ -17,6 +19,27 @@ const USER_INFO_UPDATED_AT: &str = "2022-11-11T11:00:00Z";
 const USER_INFO_EMAIL_VERIFIED: bool = true;
 const USER_INFO_PICTURE: &str = "https://github.com/primait/localauth0/blob/6f71c9318250219a9d03fb72afe4308b8824aef7/web/assets/static/media/localauth0.png";
 
+const CLIENT_NAME: &str = "Default App";
+
+const AUDIENCE_SIGNING_ALG: Algorithm = Algorithm::RS256;
+const JWKS_SIGNING_ALG: Algorithm = Algorithm::RS256;
+const AUDIENCE_ENABLE_RBAC: bool = true;
+const AUDIENCE_ADD_PERMISSIONS_IN_TOKEN: bool = true;
+
+// Same defaults as auth0: 24 hours for the APIs access tokens and 10 hours for the applications id tokens
+const ACCESS_TOKEN_LIFETIME: i64 = 86_400;
+const ID_TOKEN_LIFETIME: i64 = 36_000;
+
+const AUTHORIZATION_CODE_TTL: i64 = 600;
+
+// Same defaults as a newly created auth0 tenant: 30 days absolute and 15 days inactivity lifetime
+const REFRESH_TOKEN_ROTATION: bool = true;
+const REFRESH_TOKEN_ABSOLUTE_LIFETIME: i64 = 2_592_000;
+const REFRESH_TOKEN_INACTIVITY_LIFETIME: i64 = 1_296_000;
+const REFRESH_TOKEN_REUSE_INTERVAL: i64 = 0;
+
+const WEBHOOK_TIMEOUT: u64 = 5_000;
+
 const HTTP_PORT: u16 = 3000;
 const HTTPS_PORT: u16 = 3001;
 
@@ -75,6 +98,66 @@ pub fn user_info_picture() -> String {
     USER_INFO_PICTURE.to_string()
 }
 
+pub fn client_id() -> String {
+    CLIENT_ID_VALUE.to_string()
+}
+
+pub fn client_secret() -> String {
+    CLIENT_SECRET_VALUE.to_string()
+}
+
+pub fn client_name() -> String {
+    CLIENT_NAME.to_string()
+}
+
+pub fn audience_signing_alg() -> Algorithm {
+    AUDIENCE_SIGNING_ALG
+}
+
+pub fn jwks_signing_algs() -> Vec<Algorithm> {
+    vec![JWKS_SIGNING_ALG]
+}
+
+pub fn audience_enable_rbac() -> bool {
+    AUDIENCE_ENABLE_RBAC
+}
+
+pub fn audience_add_permissions_in_token() -> bool {
+    AUDIENCE_ADD_PERMISSIONS_IN_TOKEN
+}
+
+pub fn access_token_lifetime() -> i64 {
+    ACCESS_TOKEN_LIFETIME
+}
+
+pub fn id_token_lifetime() -> i64 {
+    ID_TOKEN_LIFETIME
+}
+
+pub fn authorization_code_ttl() -> i64 {
+    AUTHORIZATION_CODE_TTL
+}
+
+pub fn refresh_token_rotation() -> bool {
+    REFRESH_TOKEN_ROTATION
+}
+
+pub fn refresh_token_absolute_lifetime() -> i64 {
+    REFRESH_TOKEN_ABSOLUTE_LIFETIME
+}
+
+pub fn refresh_token_inactivity_lifetime() -> i64 {
+    REFRESH_TOKEN_INACTIVITY_LIFETIME
+}
+
+pub fn refresh_token_reuse_interval() -> i64 {
+    REFRESH_TOKEN_REUSE_INTERVAL
+}
+
+pub fn webhook_timeout() -> u64 {
+    WEBHOOK_TIMEOUT
+}
+
 pub fn http_port() -> u16 {
     HTTP_PORT
 }
@@ -1,7 +1,8 @@
 use chrono::Utc;
 use serde::Serialize;
 
-use super::{Issuer, UserInfo};
+use super::{defaults, Issuer, UserInfo};
+use crate::config::CustomField;
 
 #[derive(Debug, Serialize)]
 pub struct IdTokenClaims {
@@ -23,10 +24,22 @@ impl IdTokenClaims {
             sid: "session_id".to_string(),
             user_info,
             iat: Some(Utc::now().timestamp()),
-            exp: Some(Utc::now().timestamp() + 60000),
+            exp: Some(Utc::now().timestamp() + defaults::id_token_lifetime()),
             nonce,
         }
     }
+
+    /// Token lifetime in seconds, starting from the issue time
+    pub fn with_lifetime(self, lifetime: i64) -> Self {
+        Self {
+            exp: self.iat.map(|iat| iat + lifetime),
+            ..self
+        }
+    }
+
+    pub fn custom_fields_mut(&mut self) -> &mut Vec<CustomField> {
+        self.user_info.custom_fields.get_or_insert_with(Vec::new)
+    }
 }
 
 #[cfg(test)]
@@ -35,7 +48,7 @@ mod tests {
     use serde_json::{json, Value};
 
     use crate::config::Config;
-    use crate::model::IdTokenClaims;
+    use crate::model::{defaults, IdTokenClaims};
 
     #[test]
     fn id_token_serialization() {
@@ -95,7 +108,7 @@ mod tests {
             "picture": config.user_info().picture(),
             "updated_at": config.user_info().updated_at().to_rfc3339_opts(SecondsFormat::Millis, true),
             "iat": now.timestamp(),
-            "exp": now.timestamp() + 60000,
+            "exp": now.timestamp() + defaults::id_token_lifetime(),
             "nonce": nonce,
         });
 
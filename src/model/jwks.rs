@@ -1,107 +1,652 @@
 use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use openssl::pkey::Private;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{Id, PKey, Private};
 use openssl::rsa::Rsa;
-use rand::seq::IndexedRandom;
+use openssl::x509::X509;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 use uuid::Uuid;
 
+use crate::config::KeyConfig;
 use crate::error::Error;
 use crate::model::certificates;
 
+/// HS algorithms sign with a shared secret instead of a key of the jwks
+pub fn is_symmetric_alg(alg: Algorithm) -> bool {
+    matches!(alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512)
+}
+
+/// Encodes with a shared secret, setting the `typ` header. Such tokens carry no `kid`, as the secret is not published
+pub fn encode_with_secret<T: Serialize>(t: &T, alg: Algorithm, secret: &str, typ: &str) -> Result<String, Error> {
+    let mut header: Header = Header::new(alg);
+    header.typ = Some(typ.to_string());
+    Ok(jsonwebtoken::encode(
+        &header,
+        &t,
+        &EncodingKey::from_secret(secret.as_bytes()),
+    )?)
+}
+
+/// Verifies a token signed with the secret of the given audience
+pub fn decode_with_secret<T: DeserializeOwned>(
+    token: &str,
+    alg: Algorithm,
+    secret: &str,
+    audience: &str,
+) -> Result<T, Error> {
+    let mut validation: Validation = Validation::new(alg);
+    validation.set_audience(&[audience]);
+
+    Ok(jsonwebtoken::decode(token, &DecodingKey::from_secret(secret.as_bytes()), &validation)?.claims)
+}
+
+/// State of a signing key along its lifecycle, see <https://auth0.com/docs/get-started/tenant-settings/signing-keys>.
+/// Every algorithm has a single current key, the only one signing tokens, and a next key, published ahead of the
+/// rotation promoting it to current. The key current before the last rotation stays published as previous until
+/// the following rotation or until it is revoked.
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyState {
+    #[default]
+    Next,
+    Current,
+    Previous,
+    Revoked,
+}
+
+impl KeyState {
+    /// Order keys are published in: current keys first, then next and previous ones
+    pub fn rank(&self) -> u8 {
+        match self {
+            KeyState::Current => 0,
+            KeyState::Next => 1,
+            KeyState::Previous => 2,
+            KeyState::Revoked => 3,
+        }
+    }
+}
+
+/// How the `kid` of generated keys is chosen
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KidFormat {
+    /// A random UUID
+    #[default]
+    Uuid,
+    /// The JWK thumbprint, see <https://datatracker.ietf.org/doc/html/rfc7638>
+    Thumbprint,
+}
+
+/// Lifecycle of a signing key, kept along with the private key but never published
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct KeyLifecycle {
+    #[serde(default)]
+    pub state: KeyState,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_since: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub current_until: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Jwks {
     pub keys: Vec<Jwk>,
 }
 
 impl Jwks {
-    pub fn new() -> Result<Self, Error> {
-        Ok(Self {
-            keys: (1..=3).map(|_| Jwk::new()).collect::<Result<Vec<Jwk>, Error>>()?,
-        })
+    pub fn new(algs: &[Algorithm], kid_format: KidFormat) -> Result<Self, Error> {
+        Self { keys: vec![] }.with_missing_keys(algs, kid_format)
+    }
+
+    /// Makes sure every algorithm, the given ones and the ones of the existing keys, has a current and a next key.
+    /// Keys still waiting for a state, e.g. configured ones, become in order the current key, the next key and
+    /// previous keys; keys are generated when missing.
+    pub fn with_missing_keys(mut self, algs: &[Algorithm], kid_format: KidFormat) -> Result<Self, Error> {
+        let mut all_algs: Vec<Algorithm> = algs.to_vec();
+        for jwk in &self.keys {
+            let alg: Algorithm = jwk.algorithm()?;
+            if !all_algs.contains(&alg) {
+                all_algs.push(alg);
+            }
+        }
+
+        let now: DateTime<Utc> = Utc::now();
+        for alg in all_algs {
+            let mut has_current: bool = self.keys_of(alg).any(|jwk| jwk.lifecycle.state == KeyState::Current);
+            let mut has_next: bool = false;
+
+            for jwk in self.keys.iter_mut().filter(|jwk| jwk.is_alg(alg)) {
+                if jwk.lifecycle.state != KeyState::Next {
+                    continue;
+                }
+
+                if !has_current {
+                    jwk.lifecycle.state = KeyState::Current;
+                    jwk.lifecycle.current_since = Some(now);
+                    has_current = true;
+                } else if !has_next {
+                    has_next = true;
+                } else {
+                    jwk.lifecycle.state = KeyState::Previous;
+                }
+            }
+
+            if !has_current {
+                let mut jwk: Jwk = Jwk::new(alg, kid_format)?;
+                jwk.lifecycle.state = KeyState::Current;
+                jwk.lifecycle.current_since = Some(now);
+                self.keys.push(jwk);
+            }
+            if !has_next {
+                self.keys.push(Jwk::new(alg, kid_format)?);
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Keys to be published, i.e. all but the revoked ones, current keys first, then next and previous ones
+    pub fn published(&self) -> Self {
+        let mut keys: Vec<Jwk> = self
+            .keys
+            .iter()
+            .filter(|jwk| jwk.lifecycle.state != KeyState::Revoked)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|jwk| jwk.lifecycle.state.rank());
+
+        Self { keys }
     }
 
     pub fn find(&self, kid: String) -> Option<Jwk> {
         self.keys.iter().find(|jwk| jwk.kid == kid).cloned()
     }
 
-    pub fn random_jwk(&self) -> Result<Jwk, Error> {
-        self.keys.choose(&mut rand::rng()).ok_or(Error::EmptyJwks).cloned()
+    /// Current key of the first algorithm having one
+    pub fn current_jwk(&self) -> Result<Jwk, Error> {
+        self.keys
+            .iter()
+            .find(|jwk| jwk.lifecycle.state == KeyState::Current)
+            .cloned()
+            .ok_or(Error::EmptyJwks)
+    }
+
+    /// The key signing the tokens of the given algorithm
+    pub fn current_jwk_for(&self, alg: Algorithm) -> Result<Jwk, Error> {
+        self.keys_of(alg)
+            .find(|jwk| jwk.lifecycle.state == KeyState::Current)
+            .cloned()
+            .ok_or(Error::MissingSigningKey(alg))
     }
 
     pub fn parse<T: DeserializeOwned>(&self, token: &str, audience: &[impl ToString]) -> Result<T, Error> {
         let header: Header = jsonwebtoken::decode_header(token)?;
 
         if let Some(jwk) = header.kid.and_then(|kid| self.find(kid)) {
-            let mut validation: Validation = Validation::new(Algorithm::from_str(jwk.alg())?);
+            let mut validation: Validation = Validation::new(jwk.algorithm()?);
 
-            if !audience.is_empty() {
+            if audience.is_empty() {
+                validation.validate_aud = false;
+            } else {
                 validation.set_audience(audience);
             }
 
-            let decoding_key: DecodingKey = DecodingKey::from_rsa_components(jwk.modulus(), jwk.exponent())?;
-            Ok(jsonwebtoken::decode(token, &decoding_key, &validation)?.claims)
+            Ok(jsonwebtoken::decode(token, &jwk.decoding_key()?, &validation)?.claims)
         } else {
             Err(Error::JwtMissingKid)
         }
     }
 
-    pub fn rotate_keys(&self) -> Result<Self, Error> {
+    /// For every algorithm the next key becomes the current one, the current key becomes the previous one and a new
+    /// next key is generated. The former previous keys are retired.
+    pub fn rotate_keys(&self, algs: &[Algorithm], kid_format: KidFormat, now: DateTime<Utc>) -> Result<Self, Error> {
         let mut keys: Vec<Jwk> = self.keys.clone();
-        keys.insert(0, Jwk::new()?);
-        keys.pop();
-        Ok(Self { keys })
+
+        for alg in algs {
+            keys.retain(|jwk| !(jwk.is_alg(*alg) && jwk.lifecycle.state == KeyState::Previous));
+
+            for jwk in keys.iter_mut().filter(|jwk| jwk.is_alg(*alg)) {
+                match jwk.lifecycle.state {
+                    KeyState::Current => {
+                        jwk.lifecycle.state = KeyState::Previous;
+                        jwk.lifecycle.current_until = Some(now);
+                    }
+                    KeyState::Next => {
+                        jwk.lifecycle.state = KeyState::Current;
+                        jwk.lifecycle.current_since = Some(now);
+                    }
+                    KeyState::Previous | KeyState::Revoked => {}
+                }
+            }
+        }
+
+        Self { keys }.with_missing_keys(algs, kid_format)
     }
 
-    pub fn revoke_keys(&self) -> Result<Self, Error> {
-        Jwks::new()
+    /// Retires the previous keys which stopped being current before the given instant
+    pub fn retire_previous_keys(&self, current_until: DateTime<Utc>) -> Self {
+        let mut keys: Vec<Jwk> = self.keys.clone();
+        keys.retain(|jwk| {
+            jwk.lifecycle.state != KeyState::Previous
+                || jwk.lifecycle.current_until.is_none_or(|until| until > current_until)
+        });
+
+        Self { keys }
+    }
+
+    /// Revokes a single key, which stops being published. The current key can't be revoked: rotate the keys first.
+    /// A revoked next key is replaced by a new one.
+    pub fn revoke_key(&self, kid: &str, algs: &[Algorithm], kid_format: KidFormat) -> Result<Self, Error> {
+        let mut keys: Vec<Jwk> = self.keys.clone();
+        let jwk: &mut Jwk = keys
+            .iter_mut()
+            .find(|jwk| jwk.kid == kid)
+            .ok_or_else(|| Error::NotFound(format!("Signing key '{kid}' not found")))?;
+
+        match jwk.lifecycle.state {
+            KeyState::Current => {
+                return Err(Error::InvalidRequest(
+                    "The current signing key can't be revoked, rotate the keys first".to_string(),
+                ))
+            }
+            KeyState::Revoked => return Err(Error::Conflict(format!("Signing key '{kid}' is already revoked"))),
+            KeyState::Next | KeyState::Previous => {
+                jwk.lifecycle.state = KeyState::Revoked;
+                jwk.lifecycle.revoked_at = Some(Utc::now());
+            }
+        }
+
+        Self { keys }.with_missing_keys(algs, kid_format)
+    }
+
+    /// Revokes every key and generates new current and next keys
+    pub fn revoke_keys(&self, algs: &[Algorithm], kid_format: KidFormat) -> Result<Self, Error> {
+        let now: DateTime<Utc> = Utc::now();
+        let mut keys: Vec<Jwk> = self.keys.clone();
+
+        for jwk in keys.iter_mut().filter(|jwk| jwk.lifecycle.state != KeyState::Revoked) {
+            if jwk.lifecycle.state == KeyState::Current {
+                jwk.lifecycle.current_until = Some(now);
+            }
+            jwk.lifecycle.state = KeyState::Revoked;
+            jwk.lifecycle.revoked_at = Some(now);
+        }
+
+        Self { keys }.with_missing_keys(algs, kid_format)
+    }
+
+    /// Keys signing with the given algorithm, revoked ones excluded
+    fn keys_of(&self, alg: Algorithm) -> impl Iterator<Item = &Jwk> {
+        self.keys
+            .iter()
+            .filter(move |jwk| jwk.is_alg(alg) && jwk.lifecycle.state != KeyState::Revoked)
+    }
+}
+
+impl TryFrom<&KeyConfig> for Jwk {
+    type Error = Error;
+
+    fn try_from(value: &KeyConfig) -> Result<Self, Self::Error> {
+        let file_stem = || {
+            value
+                .path()
+                .as_ref()
+                .and_then(|path| path.file_stem())
+                .map(|stem| stem.to_string_lossy().to_string())
+        };
+
+        match (value.path(), value.pem(), value.jwk()) {
+            (Some(path), None, None) if path.extension().is_some_and(|extension| extension == "json") => {
+                let private_jwk: PrivateJwk = serde_json::from_slice(&std::fs::read(path)?)?;
+                let kid: Option<String> = value
+                    .kid()
+                    .clone()
+                    .or_else(|| private_jwk.kid.clone())
+                    .or_else(file_stem);
+                Jwk::from_private_jwk(&private_jwk, *value.alg(), kid)
+            }
+            (Some(path), None, None) => Jwk::from_pem(
+                &std::fs::read(path)?,
+                *value.alg(),
+                value.kid().clone().or_else(file_stem).unwrap_or_default(),
+            ),
+            (None, Some(pem), None) => {
+                let kid: String = value
+                    .kid()
+                    .clone()
+                    .ok_or_else(|| Error::InvalidSigningKey("inline PEM keys require a 'kid'".to_string()))?;
+                Jwk::from_pem(pem.as_bytes(), *value.alg(), kid)
+            }
+            (None, None, Some(private_jwk)) => Jwk::from_private_jwk(private_jwk, *value.alg(), value.kid().clone()),
+            _ => Err(Error::InvalidSigningKey(
+                "exactly one of 'path', 'pem' and 'jwk' must be set".to_string(),
+            )),
+        }
     }
 }
 
+/// Private key as in <https://datatracker.ietf.org/doc/html/rfc7518#section-6>, the format keys are loaded from and
+/// persisted to
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PrivateJwk {
+    kty: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    alg: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    e: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    d: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    p: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    q: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dp: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dq: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    qi: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x5c: Option<Vec<String>>,
+}
+
+/// Public key as in <https://datatracker.ietf.org/doc/html/rfc7518#section-6>: `n` and `e` for RSA keys, `crv`,
+/// `x` and `y` for EC keys and `crv` and `x` for OKP (Ed25519) keys
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Jwk {
     kty: String,
-    n: String,
-    e: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    n: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    e: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    crv: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    y: Option<String>,
     alg: String,
     kid: String,
     r#use: String,
     x5c: Vec<String>,
+    /// SHA-1 thumbprint of the certificate
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    x5t: Option<String>,
+    /// SHA-256 thumbprint of the certificate
+    #[serde(rename = "x5t#S256", default, skip_serializing_if = "Option::is_none")]
+    x5t_s256: Option<String>,
     #[serde(skip_serializing, skip_deserializing)]
     private_key_pem: Vec<u8>,
+    #[serde(skip_serializing, skip_deserializing)]
+    lifecycle: KeyLifecycle,
 }
 
 impl Jwk {
-    pub fn new() -> Result<Jwk, Error> {
-        let key_pair = certificates::generate_private_key()?;
-        let modulus = key_pair.rsa()?.n().to_vec();
-        let exponent = key_pair.rsa()?.e().to_vec();
-
-        let x509 = certificates::generate_certificate(&key_pair)?;
-        let x509cert = BASE64_STANDARD.encode(x509.to_der()?);
-
-        Ok(Self {
-            kty: "RSA".to_string(),
-            n: base64_url::encode(&modulus),
-            e: base64_url::encode(&exponent),
-            alg: "RS256".to_string(),
-            kid: Uuid::new_v4().to_string(),
+    pub fn new(alg: Algorithm, kid_format: KidFormat) -> Result<Jwk, Error> {
+        let mut jwk: Jwk = Self::from_key_pair(
+            &certificates::generate_signing_key(alg)?,
+            Some(alg),
+            Uuid::new_v4().to_string(),
+            None,
+        )?;
+
+        if kid_format == KidFormat::Thumbprint {
+            jwk.kid = jwk.thumbprint()?;
+        }
+
+        Ok(jwk)
+    }
+
+    /// Jwk of an existing key pair. The algorithm defaults to the one matching the key type, and a certificate is
+    /// generated when no `x5c` is given.
+    pub fn from_key_pair(
+        key_pair: &PKey<Private>,
+        alg: Option<Algorithm>,
+        kid: String,
+        x5c: Option<Vec<String>>,
+    ) -> Result<Jwk, Error> {
+        let x5c: Vec<String> = match x5c {
+            Some(x5c) => x5c,
+            None => vec![BASE64_STANDARD.encode(certificates::generate_certificate(key_pair)?.to_der()?)],
+        };
+
+        let mut jwk: Jwk = Self {
+            kty: String::new(),
+            n: None,
+            e: None,
+            crv: None,
+            x: None,
+            y: None,
+            alg: String::new(),
+            kid,
             r#use: "sig".to_string(),
-            x5c: vec![x509cert],
+            x5c,
+            x5t: None,
+            x5t_s256: None,
             private_key_pem: key_pair.private_key_to_pem_pkcs8()?,
-        })
+            lifecycle: KeyLifecycle::default(),
+        };
+
+        let key_alg: Algorithm = match key_pair.id() {
+            Id::EC => {
+                let ec_key = key_pair.ec_key()?;
+                let (mut x, mut y) = (BigNum::new()?, BigNum::new()?);
+                let mut context = BigNumContext::new()?;
+                ec_key
+                    .public_key()
+                    .affine_coordinates(ec_key.group(), &mut x, &mut y, &mut context)?;
+                // Coordinates are as long as the curve order, see <https://datatracker.ietf.org/doc/html/rfc7518#section-6.2.1.2>
+                let length: i32 = (ec_key.group().degree() as i32 + 7) / 8;
+                let (crv, key_alg) = match ec_key.group().curve_name() {
+                    Some(Nid::X9_62_PRIME256V1) => ("P-256", Algorithm::ES256),
+                    Some(Nid::SECP384R1) => ("P-384", Algorithm::ES384),
+                    _ => return Err(Error::InvalidSigningKey("unsupported elliptic curve".to_string())),
+                };
+
+                jwk.kty = "EC".to_string();
+                jwk.crv = Some(crv.to_string());
+                jwk.x = Some(base64_url::encode(&x.to_vec_padded(length)?));
+                jwk.y = Some(base64_url::encode(&y.to_vec_padded(length)?));
+                key_alg
+            }
+            Id::ED25519 => {
+                jwk.kty = "OKP".to_string();
+                jwk.crv = Some("Ed25519".to_string());
+                jwk.x = Some(base64_url::encode(&key_pair.raw_public_key()?));
+                Algorithm::EdDSA
+            }
+            Id::RSA => {
+                let rsa = key_pair.rsa()?;
+                jwk.kty = "RSA".to_string();
+                jwk.n = Some(base64_url::encode(&rsa.n().to_vec()));
+                jwk.e = Some(base64_url::encode(&rsa.e().to_vec()));
+                Algorithm::RS256
+            }
+            _ => return Err(Error::InvalidSigningKey("unsupported key type".to_string())),
+        };
+
+        // RSA keys sign with both the RS and PS algorithms
+        let alg: Algorithm = alg.unwrap_or(key_alg);
+        let rsa_algs: [Algorithm; 6] = [
+            Algorithm::RS256,
+            Algorithm::RS384,
+            Algorithm::RS512,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+        ];
+        if alg != key_alg && !(rsa_algs.contains(&alg) && rsa_algs.contains(&key_alg)) {
+            return Err(Error::InvalidSigningKey(format!(
+                "a {} key can't sign with the {alg:?} algorithm",
+                jwk.kty
+            )));
+        }
+        jwk.alg = format!("{alg:?}");
+
+        let certificate: X509 = jwk.certificate()?;
+        jwk.x5t = Some(base64_url::encode(&certificate.digest(MessageDigest::sha1())?));
+        jwk.x5t_s256 = Some(base64_url::encode(&certificate.digest(MessageDigest::sha256())?));
+
+        Ok(jwk)
+    }
+
+    /// Jwk of a PEM encoded private key, either PKCS#8, PKCS#1 or SEC1
+    pub fn from_pem(pem: &[u8], alg: Option<Algorithm>, kid: String) -> Result<Jwk, Error> {
+        Self::from_key_pair(&PKey::private_key_from_pem(pem)?, alg, kid, None)
+    }
+
+    /// Jwk of a private jwk, as in <https://datatracker.ietf.org/doc/html/rfc7518#section-6>
+    pub fn from_private_jwk(
+        private_jwk: &PrivateJwk,
+        alg: Option<Algorithm>,
+        kid: Option<String>,
+    ) -> Result<Jwk, Error> {
+        let member = |member: &Option<String>, name: &str| -> Result<BigNum, Error> {
+            let bytes: Vec<u8> = member
+                .as_deref()
+                .and_then(|value| base64_url::decode(value).ok())
+                .ok_or_else(|| Error::InvalidSigningKey(format!("missing or invalid '{name}' member")))?;
+            Ok(BigNum::from_slice(&bytes)?)
+        };
+
+        let key_pair: PKey<Private> = match private_jwk.kty.as_str() {
+            "RSA" => PKey::from_rsa(Rsa::from_private_components(
+                member(&private_jwk.n, "n")?,
+                member(&private_jwk.e, "e")?,
+                member(&private_jwk.d, "d")?,
+                member(&private_jwk.p, "p")?,
+                member(&private_jwk.q, "q")?,
+                member(&private_jwk.dp, "dp")?,
+                member(&private_jwk.dq, "dq")?,
+                member(&private_jwk.qi, "qi")?,
+            )?)?,
+            "EC" => {
+                let curve: Nid = match private_jwk.crv.as_deref() {
+                    Some("P-256") => Nid::X9_62_PRIME256V1,
+                    Some("P-384") => Nid::SECP384R1,
+                    _ => return Err(Error::InvalidSigningKey("unsupported elliptic curve".to_string())),
+                };
+                let group = EcGroup::from_curve_name(curve)?;
+                let d: BigNum = member(&private_jwk.d, "d")?;
+                let mut public_key = EcPoint::new(&group)?;
+                let mut context = BigNumContext::new()?;
+                public_key.mul_generator2(&group, &d, &mut context)?;
+                PKey::from_ec_key(EcKey::from_private_components(&group, &d, &public_key)?)?
+            }
+            "OKP" if private_jwk.crv.as_deref() == Some("Ed25519") => {
+                // Raw 32 bytes key, whose leading zeros a BigNum would strip
+                let d: Vec<u8> = private_jwk
+                    .d
+                    .as_deref()
+                    .and_then(|d| base64_url::decode(d).ok())
+                    .ok_or_else(|| Error::InvalidSigningKey("missing or invalid 'd' member".to_string()))?;
+                PKey::private_key_from_raw_bytes(&d, Id::ED25519)?
+            }
+            kty => return Err(Error::InvalidSigningKey(format!("unsupported key type '{kty}'"))),
+        };
+
+        let alg: Option<Algorithm> = match (alg, &private_jwk.alg) {
+            (Some(alg), _) => Some(alg),
+            (None, Some(alg)) => Some(Algorithm::from_str(alg)?),
+            (None, None) => None,
+        };
+        let kid: String = kid
+            .or_else(|| private_jwk.kid.clone())
+            .ok_or_else(|| Error::InvalidSigningKey("missing 'kid' member".to_string()))?;
+
+        Self::from_key_pair(&key_pair, alg, kid, private_jwk.x5c.clone())
+    }
+
+    /// Private jwk of the key, to be persisted
+    pub fn to_private_jwk(&self) -> Result<PrivateJwk, Error> {
+        let key_pair: PKey<Private> = PKey::private_key_from_pem(&self.private_key_pem)?;
+        let encode = |bytes: Vec<u8>| Some(base64_url::encode(&bytes));
+
+        let mut private_jwk: PrivateJwk = PrivateJwk {
+            kty: self.kty.clone(),
+            kid: Some(self.kid.clone()),
+            alg: Some(self.alg.clone()),
+            crv: self.crv.clone(),
+            x: self.x.clone(),
+            y: self.y.clone(),
+            n: self.n.clone(),
+            e: self.e.clone(),
+            x5c: Some(self.x5c.clone()),
+            ..PrivateJwk::default()
+        };
+
+        match key_pair.id() {
+            Id::EC => {
+                let ec_key = key_pair.ec_key()?;
+                let length: i32 = (ec_key.group().degree() as i32 + 7) / 8;
+                private_jwk.d = encode(ec_key.private_key().to_vec_padded(length)?);
+            }
+            Id::ED25519 => private_jwk.d = encode(key_pair.raw_private_key()?),
+            _ => {
+                let rsa = key_pair.rsa()?;
+                private_jwk.d = encode(rsa.d().to_vec());
+                private_jwk.p = rsa.p().map(|p| base64_url::encode(&p.to_vec()));
+                private_jwk.q = rsa.q().map(|q| base64_url::encode(&q.to_vec()));
+                private_jwk.dp = rsa.dmp1().map(|dp| base64_url::encode(&dp.to_vec()));
+                private_jwk.dq = rsa.dmq1().map(|dq| base64_url::encode(&dq.to_vec()));
+                private_jwk.qi = rsa.iqmp().map(|qi| base64_url::encode(&qi.to_vec()));
+            }
+        }
+
+        Ok(private_jwk)
     }
 
     pub fn encode<T: Serialize>(&self, t: &T) -> Result<String, Error> {
-        let mut header: Header = Header::new(Algorithm::RS256);
+        self.encode_with_type(t, "JWT")
+    }
+
+    /// Encodes setting the `typ` header, e.g. `at+jwt` for <https://datatracker.ietf.org/doc/html/rfc9068#section-2.1>
+    pub fn encode_with_type<T: Serialize>(&self, t: &T, typ: &str) -> Result<String, Error> {
+        let mut header: Header = Header::new(self.algorithm()?);
+        header.typ = Some(typ.to_string());
         header.kid = Some(self.kid().to_string());
-        let key: EncodingKey = EncodingKey::from_rsa_pem(self.private_key_pem())?;
+        let key: EncodingKey = match self.kty.as_str() {
+            "EC" => EncodingKey::from_ec_pem(self.private_key_pem())?,
+            "OKP" => EncodingKey::from_ed_pem(self.private_key_pem())?,
+            _ => EncodingKey::from_rsa_pem(self.private_key_pem())?,
+        };
         Ok(jsonwebtoken::encode(&header, &t, &key)?)
     }
 
+    /// Key verifying the signatures, built from the public members of the jwk
+    pub fn decoding_key(&self) -> Result<DecodingKey, Error> {
+        let member = |member: &Option<String>, name: &str| -> Result<String, Error> {
+            member
+                .clone()
+                .ok_or_else(|| Error::InvalidToken(format!("Jwk '{}' has no '{}' member", self.kid, name)))
+        };
+
+        Ok(match self.kty.as_str() {
+            "EC" => DecodingKey::from_ec_components(&member(&self.x, "x")?, &member(&self.y, "y")?)?,
+            "OKP" => DecodingKey::from_ed_components(&member(&self.x, "x")?)?,
+            _ => DecodingKey::from_rsa_components(&member(&self.n, "n")?, &member(&self.e, "e")?)?,
+        })
+    }
+
     pub fn kid(&self) -> &str {
         &self.kid
     }
@@ -110,19 +655,123 @@ impl Jwk {
         &self.alg
     }
 
-    pub fn modulus(&self) -> &str {
-        &self.n
+    pub fn algorithm(&self) -> Result<Algorithm, Error> {
+        Ok(Algorithm::from_str(&self.alg)?)
+    }
+
+    fn is_alg(&self, alg: Algorithm) -> bool {
+        self.algorithm().is_ok_and(|key_alg| key_alg == alg)
+    }
+
+    pub fn lifecycle(&self) -> &KeyLifecycle {
+        &self.lifecycle
+    }
+
+    pub fn set_lifecycle(&mut self, lifecycle: KeyLifecycle) {
+        self.lifecycle = lifecycle;
     }
 
-    pub fn exponent(&self) -> &str {
-        &self.e
+    /// JWK thumbprint as in <https://datatracker.ietf.org/doc/html/rfc7638>: the SHA-256 digest of the required public
+    /// members, ordered lexicographically and serialized without whitespaces
+    pub fn thumbprint(&self) -> Result<String, Error> {
+        let member = |member: &Option<String>| member.clone().unwrap_or_default();
+        // serde_json maps are sorted, so members are serialized in lexicographic order
+        let required_members: serde_json::Value = match self.kty.as_str() {
+            "EC" => serde_json::json!({
+                "crv": member(&self.crv),
+                "kty": self.kty,
+                "x": member(&self.x),
+                "y": member(&self.y),
+            }),
+            "OKP" => serde_json::json!({ "crv": member(&self.crv), "kty": self.kty, "x": member(&self.x) }),
+            _ => serde_json::json!({ "e": member(&self.e), "kty": self.kty, "n": member(&self.n) }),
+        };
+
+        Ok(base64_url::encode(&openssl::sha::sha256(
+            required_members.to_string().as_bytes(),
+        )))
+    }
+
+    /// Certificate of the key, the first one of the `x5c` chain
+    pub fn certificate(&self) -> Result<X509, Error> {
+        let der: Vec<u8> = self
+            .x5c
+            .first()
+            .and_then(|certificate| BASE64_STANDARD.decode(certificate).ok())
+            .ok_or_else(|| Error::InvalidSigningKey(format!("Jwk '{}' has no valid certificate", self.kid)))?;
+        Ok(X509::from_der(&der)?)
+    }
+
+    pub fn kty(&self) -> &str {
+        &self.kty
     }
 
     pub fn private_key_pem(&self) -> &Vec<u8> {
         &self.private_key_pem
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+
+    use super::*;
+
+    fn kid_in(jwks: &Jwks, state: KeyState) -> String {
+        jwks.keys
+            .iter()
+            .find(|jwk| jwk.lifecycle.state == state)
+            .map(|jwk| jwk.kid.clone())
+            .unwrap()
+    }
+
+    #[test]
+    fn rotate_keys_moves_every_key_to_its_following_state_test() {
+        let rotated_at: DateTime<Utc> = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let jwks: Jwks = Jwks::new(&[Algorithm::ES256], KidFormat::Uuid).unwrap();
+        let current: String = kid_in(&jwks, KeyState::Current);
+        let next: String = kid_in(&jwks, KeyState::Next);
+
+        let rotated: Jwks = jwks
+            .rotate_keys(&[Algorithm::ES256], KidFormat::Uuid, rotated_at)
+            .unwrap();
+        assert_eq!(rotated.keys.len(), 3);
+        assert_eq!(kid_in(&rotated, KeyState::Current), next);
+        assert_eq!(kid_in(&rotated, KeyState::Previous), current);
+        assert_ne!(kid_in(&rotated, KeyState::Next), next);
+
+        let previous: Jwk = rotated.find(current.clone()).unwrap();
+        assert_eq!(previous.lifecycle.current_until, Some(rotated_at));
+        assert_eq!(
+            rotated.find(next.clone()).unwrap().lifecycle.current_since,
+            Some(rotated_at)
+        );
+
+        // The following rotation retires the former previous key
+        let rotated_again: Jwks = rotated
+            .rotate_keys(&[Algorithm::ES256], KidFormat::Uuid, rotated_at + TimeDelta::hours(1))
+            .unwrap();
+        assert_eq!(rotated_again.keys.len(), 3);
+        assert!(rotated_again.find(current).is_none());
+        assert_eq!(kid_in(&rotated_again, KeyState::Previous), next);
+    }
+
+    #[test]
+    fn retire_previous_keys_only_retires_the_ones_rotated_out_before_the_given_instant_test() {
+        let rotated_at: DateTime<Utc> = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let jwks: Jwks = Jwks::new(&[Algorithm::ES256], KidFormat::Uuid)
+            .unwrap()
+            .rotate_keys(&[Algorithm::ES256], KidFormat::Uuid, rotated_at)
+            .unwrap();
+        let previous: String = kid_in(&jwks, KeyState::Previous);
+
+        // Still within the overlap
+        let retired: Jwks = jwks.retire_previous_keys(rotated_at - TimeDelta::seconds(1));
+        assert!(retired.find(previous.clone()).is_some());
 
-    pub fn rsa(&self) -> Result<Rsa<Private>, Error> {
-        Ok(Rsa::private_key_from_pem(&self.private_key_pem)?)
+        let retired: Jwks = jwks.retire_previous_keys(rotated_at);
+        assert!(retired.find(previous).is_none());
+        assert_eq!(retired.keys.len(), 2);
+        assert!(retired.current_jwk_for(Algorithm::ES256).is_ok());
     }
 }
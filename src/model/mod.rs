@@ -1,21 +1,39 @@
 pub use crate::app_data::*;
+pub use action::*;
+pub use audience::*;
+pub use authorization::*;
 pub use claims::*;
+pub use client::*;
 pub use id_token::*;
 pub use jwks::*;
 pub use openid_metadata::*;
+pub use refresh_token::*;
 pub use request::*;
 pub use response::*;
+pub use role::*;
+pub use template::*;
+pub use user::*;
 pub use user_info::*;
+pub use webhook::*;
 
+mod action;
+mod audience;
+mod authorization;
 pub mod certificates;
 mod claims;
+mod client;
 pub mod defaults;
 mod id_token;
 mod jwks;
 mod openid_metadata;
+mod refresh_token;
 mod request;
 mod response;
+mod role;
+mod template;
+mod user;
 mod user_info;
+mod webhook;
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 #[cfg_attr(test, derive(PartialEq))]
@@ -1,16 +1,32 @@
-use super::{Issuer, Jwk, TokenResponse};
+use super::{
+    CodeChallengeMethod, GrantType, Issuer, Jwk, TokenResponse, UserInfo, OFFLINE_ACCESS_SCOPE, OPENID_SCOPES,
+};
 use crate::controller;
+use crate::error::Error;
 use serde::Serialize;
 
+/// Claims always set in the id token, along with the user info ones
+const ID_TOKEN_CLAIMS: [&str; 7] = ["iss", "sub", "aud", "exp", "iat", "nonce", "azp"];
+
+/// Served both as OpenID Connect discovery document and as OAuth 2.0 authorization server metadata.
+/// See <https://openid.net/specs/openid-connect-discovery-1_0.html#ProviderMetadata> and
+/// <https://datatracker.ietf.org/doc/html/rfc8414#section-2>
 #[derive(Serialize)]
 pub struct OpenIDMetadata {
     issuer: Issuer,
     authorization_endpoint: String,
     token_endpoint: String,
+    userinfo_endpoint: String,
     jwks_uri: String,
+    scopes_supported: Vec<String>,
     response_types_supported: Vec<String>,
+    response_modes_supported: Vec<String>,
+    grant_types_supported: Vec<String>,
     subject_types_supported: Vec<String>,
     id_token_signing_alg_values_supported: Vec<String>,
+    token_endpoint_auth_methods_supported: Vec<String>,
+    claims_supported: Vec<String>,
+    code_challenge_methods_supported: Vec<String>,
 }
 
 fn endpoint_to_url(base_uri: &str, endpoint: &str) -> String {
@@ -20,22 +36,60 @@ fn endpoint_to_url(base_uri: &str, endpoint: &str) -> String {
 impl OpenIDMetadata {
     pub fn new(
         issuer: &Issuer,
-        random_jwk: &Jwk,
+        signing_jwk: &Jwk,
+        user_info: &UserInfo,
+        // Whether a public client is registered, authenticating without a secret
+        public_clients: bool,
         // The base uri for, concatenated with endpoints to generate the urls
         base_uri: &str,
-    ) -> Self {
-        let authorization_endpoint = endpoint_to_url(base_uri, controller::login::ENDPOINT);
+    ) -> Result<Self, Error> {
+        let authorization_endpoint = endpoint_to_url(base_uri, controller::AUTHORIZE_ENDPOINT);
         let token_endpoint = endpoint_to_url(base_uri, controller::token::ENDPOINT);
+        let userinfo_endpoint = endpoint_to_url(base_uri, controller::userinfo::ENDPOINT);
         let jwks_uri = endpoint_to_url(base_uri, controller::jwks::ENDPOINT);
 
-        Self {
+        let scopes_supported: Vec<String> = OPENID_SCOPES
+            .iter()
+            .chain([OFFLINE_ACCESS_SCOPE].iter())
+            .map(|scope| scope.to_string())
+            .collect();
+
+        let mut claims_supported: Vec<String> = ID_TOKEN_CLAIMS.iter().map(|claim| claim.to_string()).collect();
+        for claim in user_info.claims_for_scope(&scopes_supported.join(" "))?.keys() {
+            if !claims_supported.contains(claim) {
+                claims_supported.push(claim.to_string());
+            }
+        }
+
+        let mut token_endpoint_auth_methods_supported: Vec<String> = vec!["client_secret_post".to_string()];
+        if public_clients {
+            token_endpoint_auth_methods_supported.push("none".to_string());
+        }
+
+        Ok(Self {
             issuer: issuer.clone(),
             authorization_endpoint,
             token_endpoint,
+            userinfo_endpoint,
             jwks_uri,
+            scopes_supported,
             response_types_supported: TokenResponse::response_types_supported(),
+            // Authorization codes are returned in the query string, implicit grant tokens in the fragment
+            response_modes_supported: vec!["query".to_string(), "fragment".to_string()],
+            grant_types_supported: vec![
+                GrantType::AuthorizationCode.to_string(),
+                GrantType::ClientCredentials.to_string(),
+                GrantType::RefreshToken.to_string(),
+                "implicit".to_string(),
+            ],
             subject_types_supported: vec!["public".to_string()],
-            id_token_signing_alg_values_supported: vec![random_jwk.alg().to_string()],
-        }
+            id_token_signing_alg_values_supported: vec![signing_jwk.alg().to_string()],
+            token_endpoint_auth_methods_supported,
+            claims_supported,
+            code_challenge_methods_supported: vec![
+                CodeChallengeMethod::S256.to_string(),
+                CodeChallengeMethod::Plain.to_string(),
+            ],
+        })
     }
 }
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+
+pub const OFFLINE_ACCESS_SCOPE: &str = "offline_access";
+
+#[derive(Debug, Clone)]
+pub struct RefreshToken {
+    token: String,
+    /// Every refresh token obtained rotating another one belongs to the same family of the original one
+    family: String,
+    client_id: String,
+    audience: String,
+    scope: Option<String>,
+    subject: Option<String>,
+    family_created_at: DateTime<Utc>,
+    last_used_at: DateTime<Utc>,
+    rotated_at: Option<DateTime<Utc>>,
+}
+
+impl RefreshToken {
+    pub fn new(client_id: String, audience: String, scope: Option<String>, subject: Option<String>) -> Self {
+        let now: DateTime<Utc> = Utc::now();
+
+        Self {
+            token: uuid::Uuid::new_v4().to_string(),
+            family: uuid::Uuid::new_v4().to_string(),
+            client_id,
+            audience,
+            scope,
+            subject,
+            family_created_at: now,
+            last_used_at: now,
+            rotated_at: None,
+        }
+    }
+
+    /// Creates the next refresh token of the same family
+    pub fn rotate(&self) -> Self {
+        Self {
+            token: uuid::Uuid::new_v4().to_string(),
+            family: self.family.clone(),
+            client_id: self.client_id.clone(),
+            audience: self.audience.clone(),
+            scope: self.scope.clone(),
+            subject: self.subject.clone(),
+            family_created_at: self.family_created_at,
+            last_used_at: Utc::now(),
+            rotated_at: None,
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    pub fn audience(&self) -> &str {
+        &self.audience
+    }
+
+    pub fn scope(&self) -> Option<&str> {
+        self.scope.as_deref()
+    }
+
+    /// Subject of the user the token has been issued to, if a user was picked while logging in
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    pub fn family_created_at(&self) -> &DateTime<Utc> {
+        &self.family_created_at
+    }
+
+    pub fn last_used_at(&self) -> &DateTime<Utc> {
+        &self.last_used_at
+    }
+
+    pub fn rotated_at(&self) -> Option<&DateTime<Utc>> {
+        self.rotated_at.as_ref()
+    }
+
+    pub fn set_last_used_at(&mut self, last_used_at: DateTime<Utc>) {
+        self.last_used_at = last_used_at;
+    }
+
+    pub fn set_rotated_at(&mut self, rotated_at: DateTime<Utc>) {
+        self.rotated_at = Some(rotated_at);
+    }
+}
@@ -1,6 +1,11 @@
 use crate::config::CustomField;
+use crate::error::Error;
+use crate::model::{CodeChallengeMethod, GrantType};
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use std::collections::HashMap;
+
+const FORM_CONTENT_TYPE: &str = "application/x-www-form-urlencoded";
 
 #[derive(Deserialize)]
 #[cfg_attr(test, derive(serde::Serialize))]
@@ -8,6 +13,10 @@ pub struct ClientCredentialsTokenRequest {
     pub client_id: String,
     pub client_secret: String,
     pub audience: String,
+    /// Space separated permissions of the audience. Missing means every permission.
+    pub scope: Option<String>,
+    /// Overrides the configured token lifetimes, in seconds
+    pub expires_in: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -16,8 +25,19 @@ pub struct AuthorizationCodeTokenRequest {
     pub client_id: String,
     pub client_secret: Option<String>,
     pub code: String,
+    pub code_verifier: Option<String>,
     pub nonce: Option<String>,
     pub redirect_uri: Option<String>,
+    pub expires_in: Option<i64>,
+}
+
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct RefreshTokenRequest {
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub refresh_token: String,
+    pub expires_in: Option<i64>,
 }
 
 #[derive(Deserialize)]
@@ -26,6 +46,38 @@ pub struct AuthorizationCodeTokenRequest {
 pub enum TokenRequest {
     AuthorizationCode(AuthorizationCodeTokenRequest),
     ClientCredentials(ClientCredentialsTokenRequest),
+    RefreshToken(RefreshTokenRequest),
+}
+
+impl TokenRequest {
+    /// The token endpoint accepts both json and form url encoded bodies. The grant type is checked before parsing
+    /// the whole request in order to tell apart unsupported grant types from malformed requests.
+    pub fn from_body(content_type: &str, body: &[u8]) -> Result<Self, Error> {
+        let is_form: bool = content_type == FORM_CONTENT_TYPE;
+
+        let grant_type: Option<String> = if is_form {
+            serde_urlencoded::from_bytes::<HashMap<String, String>>(body)
+                .map_err(|e| Error::InvalidRequest(e.to_string()))?
+                .remove("grant_type")
+        } else {
+            serde_json::from_slice::<serde_json::Value>(body)
+                .map_err(|e| Error::InvalidRequest(e.to_string()))?
+                .get("grant_type")
+                .and_then(serde_json::Value::as_str)
+                .map(str::to_string)
+        };
+
+        match grant_type {
+            None => Err(Error::InvalidRequest(
+                "Missing required parameter: grant_type".to_string(),
+            )),
+            Some(grant_type) if serde_json::from_value::<GrantType>(grant_type.clone().into()).is_err() => Err(
+                Error::UnsupportedGrantType(format!("Unsupported grant type: {grant_type}")),
+            ),
+            Some(_) if is_form => serde_urlencoded::from_bytes(body).map_err(|e| Error::InvalidRequest(e.to_string())),
+            Some(_) => serde_json::from_slice(body).map_err(|e| Error::InvalidRequest(e.to_string())),
+        }
+    }
 }
 
 #[derive(Deserialize)]
@@ -35,6 +87,12 @@ pub struct PermissionsForAudienceRequest {
     pub permissions: Vec<String>,
 }
 
+#[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct UserRolesRequest {
+    pub roles: Vec<String>,
+}
+
 #[derive(Deserialize)]
 pub struct UpdateCustomClaimsRequest {
     pub custom_claims: Vec<CustomField>,
@@ -59,6 +117,17 @@ pub struct UpdateUserInfoRequest {
 }
 
 #[derive(Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
 pub struct LoginRequest {
     pub audience: String,
+    pub client_id: Option<String>,
+    pub redirect_uri: Option<String>,
+    pub scope: Option<String>,
+    pub nonce: Option<String>,
+    /// Echoed back to the client by the frontend redirect, the authorization code isn't bound to it
+    pub state: Option<String>,
+    pub code_challenge: Option<String>,
+    pub code_challenge_method: Option<CodeChallengeMethod>,
+    /// Subject of the user to log in as. When missing the default user is logged in.
+    pub user: Option<String>,
 }
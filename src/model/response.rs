@@ -7,22 +7,29 @@ use crate::BEARER;
 pub struct TokenResponse {
     access_token: String,
     id_token: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    refresh_token: Option<String>,
     scope: String,
-    expires_in: i32,
+    expires_in: i64,
     token_type: String,
 }
 
 impl TokenResponse {
-    pub fn new(access_token: String, id_token: String, scope_opt: Option<String>) -> Self {
+    pub fn new(access_token: String, id_token: String, scope_opt: Option<String>, expires_in: i64) -> Self {
         Self {
             access_token,
             id_token,
+            refresh_token: None,
             scope: scope_opt.unwrap_or_default(),
-            expires_in: 86400,
+            expires_in,
             token_type: BEARER.to_string(),
         }
     }
 
+    pub fn with_refresh_token(self, refresh_token: Option<String>) -> Self {
+        Self { refresh_token, ..self }
+    }
+
     #[cfg(test)]
     pub fn access_token(&self) -> &str {
         &self.access_token
@@ -33,12 +40,23 @@ impl TokenResponse {
         &self.id_token
     }
 
+    #[cfg(test)]
+    pub fn expires_in(&self) -> i64 {
+        self.expires_in
+    }
+
+    #[cfg(test)]
+    pub fn refresh_token(&self) -> Option<&str> {
+        self.refresh_token.as_deref()
+    }
+
     pub fn response_types_supported() -> Vec<String> {
-        vec!["token id_token".to_string()]
+        vec!["code".to_string(), "token".to_string()]
     }
 }
 
 #[derive(Serialize)]
+#[cfg_attr(test, derive(serde::Deserialize))]
 pub struct LoginResponse {
     pub code: String,
 }
@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::RoleConfig;
+
+/// A role grants its permissions, scoped by audience, to the users it is assigned to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq))]
+pub struct Role {
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default)]
+    permissions: HashMap<String, Vec<String>>,
+}
+
+impl Role {
+    pub fn new(name: String, description: Option<String>) -> Self {
+        Self {
+            name,
+            description,
+            permissions: HashMap::new(),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn set_description(&mut self, description: Option<String>) {
+        self.description = description;
+    }
+
+    pub fn permissions(&self) -> &HashMap<String, Vec<String>> {
+        &self.permissions
+    }
+
+    pub fn permissions_for(&self, audience: &str) -> &[String] {
+        self.permissions.get(audience).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn add_permission(&mut self, audience: &str, permission: &str) {
+        let permissions: &mut Vec<String> = self.permissions.entry(audience.to_string()).or_default();
+
+        if !permissions.iter().any(|p| p == permission) {
+            permissions.push(permission.to_string());
+        }
+    }
+
+    pub fn remove_permission(&mut self, audience: &str, permission: &str) {
+        if let Some(permissions) = self.permissions.get_mut(audience) {
+            permissions.retain(|p| p != permission);
+
+            if permissions.is_empty() {
+                self.permissions.remove(audience);
+            }
+        }
+    }
+}
+
+impl From<&RoleConfig> for Role {
+    fn from(value: &RoleConfig) -> Self {
+        Self {
+            name: value.name().to_string(),
+            description: value.description().clone(),
+            permissions: value.permissions().clone(),
+        }
+    }
+}
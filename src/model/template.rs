@@ -0,0 +1,341 @@
+use serde_json::{Map, Value};
+
+use crate::config::{CustomField, CustomFieldValue};
+use crate::error::Error;
+
+/// Custom claim values can reference the attributes of the token being issued, e.g. `{{user.email}}`,
+/// `{{user.app_metadata.tenant | default: "none"}}` or
+/// `{{#if grant_type == "client_credentials"}}m2m{{else}}{{user.name}}{{/if}}`.
+/// A value made only of an expression keeps the type of the referenced attribute.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Expression {
+        path: String,
+        default: Option<String>,
+    },
+    If {
+        condition: Condition,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+#[derive(Debug)]
+enum Condition {
+    Truthy(String),
+    Equals(String, String),
+    NotEquals(String, String),
+}
+
+/// Block being parsed: the root one or an open `#if`, whose `then` branch is set once `{{else}}` is found
+struct Block {
+    condition: Option<Condition>,
+    nodes: Vec<Node>,
+    then: Option<Vec<Node>>,
+}
+
+impl Block {
+    fn new(condition: Option<Condition>) -> Self {
+        Self {
+            condition,
+            nodes: vec![],
+            then: None,
+        }
+    }
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Result<Self, Error> {
+        let mut stack: Vec<Block> = vec![Block::new(None)];
+        let mut rest: &str = template;
+
+        while let Some(start) = rest.find("{{") {
+            let end: usize = rest[start..]
+                .find("}}")
+                .map(|end| start + end)
+                .ok_or_else(|| invalid_template(template, "unclosed '{{'"))?;
+
+            if start > 0 {
+                current_nodes(&mut stack).push(Node::Text(rest[..start].to_string()));
+            }
+
+            let tag: &str = rest[start + 2..end].trim();
+            if let Some(condition) = tag.strip_prefix("#if ") {
+                stack.push(Block::new(Some(Condition::parse(condition, template)?)));
+            } else if tag == "else" {
+                match stack.last_mut() {
+                    Some(block) if block.condition.is_some() && block.then.is_none() => {
+                        block.then = Some(std::mem::take(&mut block.nodes))
+                    }
+                    _ => return Err(invalid_template(template, "unexpected '{{else}}'")),
+                }
+            } else if tag == "/if" {
+                match stack.pop() {
+                    Some(Block {
+                        condition: Some(condition),
+                        nodes,
+                        then,
+                    }) if !stack.is_empty() => {
+                        let (then, otherwise) = match then {
+                            Some(then) => (then, nodes),
+                            None => (nodes, vec![]),
+                        };
+                        current_nodes(&mut stack).push(Node::If {
+                            condition,
+                            then,
+                            otherwise,
+                        });
+                    }
+                    _ => return Err(invalid_template(template, "unexpected '{{/if}}'")),
+                }
+            } else {
+                let (path, default) = parse_expression(tag, template)?;
+                current_nodes(&mut stack).push(Node::Expression { path, default });
+            }
+
+            rest = &rest[end + 2..];
+        }
+
+        if !rest.is_empty() {
+            current_nodes(&mut stack).push(Node::Text(rest.to_string()));
+        }
+
+        match stack.pop() {
+            Some(Block {
+                condition: None, nodes, ..
+            }) if stack.is_empty() => Ok(Self { nodes }),
+            _ => Err(invalid_template(template, "missing '{{/if}}'")),
+        }
+    }
+
+    pub fn render(&self, context: &Value) -> Value {
+        match self.nodes.as_slice() {
+            [Node::Expression { path, default }] => match (lookup(context, path), default) {
+                (Some(value), _) if !value.is_null() => value.clone(),
+                (_, Some(default)) => Value::String(default.clone()),
+                _ => Value::Null,
+            },
+            nodes => Value::String(render_nodes(nodes, context)),
+        }
+    }
+}
+
+impl Condition {
+    fn parse(condition: &str, template: &str) -> Result<Self, Error> {
+        let condition: &str = condition.trim();
+
+        if let Some((path, literal)) = condition.split_once("!=") {
+            Ok(Condition::NotEquals(
+                path.trim().to_string(),
+                parse_literal(literal, template)?,
+            ))
+        } else if let Some((path, literal)) = condition.split_once("==") {
+            Ok(Condition::Equals(
+                path.trim().to_string(),
+                parse_literal(literal, template)?,
+            ))
+        } else {
+            Ok(Condition::Truthy(condition.to_string()))
+        }
+    }
+
+    fn evaluate(&self, context: &Value) -> bool {
+        match self {
+            Condition::Truthy(path) => lookup(context, path).is_some_and(is_truthy),
+            Condition::Equals(path, literal) => lookup(context, path).map(to_text).as_deref() == Some(literal),
+            Condition::NotEquals(path, literal) => lookup(context, path).map(to_text).as_deref() != Some(literal),
+        }
+    }
+}
+
+/// Renders the templates found in the string values of the custom fields
+pub fn render_custom_fields(custom_fields: &[CustomField], context: &Value) -> Result<Vec<CustomField>, Error> {
+    custom_fields
+        .iter()
+        .map(|custom_field| {
+            let value: CustomFieldValue = match custom_field.value() {
+                CustomFieldValue::String(string) => match render_string(string, context)? {
+                    Value::String(string) => CustomFieldValue::String(string),
+                    value => CustomFieldValue::Json(value),
+                },
+                CustomFieldValue::Vec(vec) => CustomFieldValue::Vec(
+                    vec.iter()
+                        .map(|string| Ok(to_text(&render_string(string, context)?)))
+                        .collect::<Result<Vec<String>, Error>>()?,
+                ),
+                CustomFieldValue::Json(value) => CustomFieldValue::Json(render_json(value, context)?),
+                value => value.clone(),
+            };
+
+            Ok(custom_field.clone().with_value(value))
+        })
+        .collect()
+}
+
+/// Checks the templates of the custom fields without rendering them
+pub fn validate_custom_fields(custom_fields: &[CustomField]) -> Result<(), Error> {
+    render_custom_fields(custom_fields, &Value::Null).map(|_| ())
+}
+
+/// Plain strings, the most common values, are not parsed at all
+fn render_string(string: &str, context: &Value) -> Result<Value, Error> {
+    if string.contains("{{") {
+        Ok(Template::parse(string)?.render(context))
+    } else {
+        Ok(Value::String(string.to_string()))
+    }
+}
+
+fn render_json(value: &Value, context: &Value) -> Result<Value, Error> {
+    Ok(match value {
+        Value::String(string) => render_string(string, context)?,
+        Value::Array(array) => Value::Array(
+            array
+                .iter()
+                .map(|value| render_json(value, context))
+                .collect::<Result<Vec<Value>, Error>>()?,
+        ),
+        Value::Object(object) => Value::Object(
+            object
+                .iter()
+                .map(|(key, value)| Ok((key.clone(), render_json(value, context)?)))
+                .collect::<Result<Map<String, Value>, Error>>()?,
+        ),
+        value => value.clone(),
+    })
+}
+
+fn render_nodes(nodes: &[Node], context: &Value) -> String {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Text(text) => text.clone(),
+            Node::Expression { path, default } => match (lookup(context, path), default) {
+                (Some(value), _) if !value.is_null() => to_text(value),
+                (_, Some(default)) => default.clone(),
+                _ => String::new(),
+            },
+            Node::If {
+                condition,
+                then,
+                otherwise,
+            } => {
+                if condition.evaluate(context) {
+                    render_nodes(then, context)
+                } else {
+                    render_nodes(otherwise, context)
+                }
+            }
+        })
+        .collect()
+}
+
+fn current_nodes(stack: &mut [Block]) -> &mut Vec<Node> {
+    &mut stack
+        .last_mut()
+        .expect("the root block is never popped while parsing")
+        .nodes
+}
+
+fn parse_expression(tag: &str, template: &str) -> Result<(String, Option<String>), Error> {
+    match tag.split_once('|') {
+        None => Ok((tag.to_string(), None)),
+        Some((path, filter)) => match filter.trim().strip_prefix("default:") {
+            Some(literal) => Ok((path.trim().to_string(), Some(parse_literal(literal, template)?))),
+            None => Err(invalid_template(template, "unknown filter")),
+        },
+    }
+}
+
+fn parse_literal(literal: &str, template: &str) -> Result<String, Error> {
+    literal
+        .trim()
+        .strip_prefix('"')
+        .and_then(|literal| literal.strip_suffix('"'))
+        .map(str::to_string)
+        .ok_or_else(|| invalid_template(template, "literals must be double quoted"))
+}
+
+fn lookup<'a>(context: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(context, |value, segment| match value {
+        Value::Object(object) => object.get(segment),
+        Value::Array(array) => segment.parse::<usize>().ok().and_then(|index| array.get(index)),
+        _ => None,
+    })
+}
+
+fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(bool) => *bool,
+        Value::Number(number) => number.as_f64() != Some(0.0),
+        Value::String(string) => !string.is_empty(),
+        Value::Array(array) => !array.is_empty(),
+        Value::Object(object) => !object.is_empty(),
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn invalid_template(template: &str, reason: &str) -> Error {
+    Error::InvalidRequest(format!("Invalid template '{template}': {reason}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Template;
+
+    #[test]
+    fn render_test() {
+        let context = json!({
+            "user": { "email": "alice@example.com", "app_metadata": { "tenant": { "id": "acme" }, "admin": true } },
+            "client": { "id": "client_id" },
+            "grant_type": "authorization_code",
+        });
+        let render = |template: &str| Template::parse(template).unwrap().render(&context);
+
+        assert_eq!(render("plain"), json!("plain"));
+        assert_eq!(render("{{user.email}}"), json!("alice@example.com"));
+        assert_eq!(render("{{ user.app_metadata.tenant }}"), json!({ "id": "acme" }));
+        assert_eq!(render("{{user.app_metadata.admin}}"), json!(true));
+        assert_eq!(
+            render("{{client.id}}@{{user.app_metadata.tenant.id}}"),
+            json!("client_id@acme")
+        );
+        assert_eq!(render("{{user.missing}}"), json!(null));
+        assert_eq!(render("{{user.missing | default: \"none\"}}"), json!("none"));
+        assert_eq!(
+            render("{{#if user.app_metadata.admin}}admin{{else}}user{{/if}}"),
+            json!("admin")
+        );
+        assert_eq!(render("{{#if user.missing}}admin{{/if}}"), json!(""));
+        assert_eq!(
+            render("{{#if grant_type == \"client_credentials\"}}m2m{{else}}{{#if grant_type != \"\"}}{{user.email}}{{/if}}{{/if}}"),
+            json!("alice@example.com")
+        );
+    }
+
+    #[test]
+    fn parse_errors_test() {
+        assert!(Template::parse("{{user.email").is_err());
+        assert!(Template::parse("{{#if user.email}}").is_err());
+        assert!(Template::parse("{{/if}}").is_err());
+        assert!(Template::parse("{{else}}").is_err());
+        assert!(Template::parse("{{user.email | upper}}").is_err());
+        assert!(Template::parse("{{#if user.email == admin}}{{/if}}").is_err());
+    }
+}
@@ -0,0 +1,278 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::config::UserConfig;
+use crate::model::{Role, UserInfo};
+
+/// A user of the directory configured through `[[user]]` sections, who can be picked while logging in
+#[derive(Debug, Clone, Serialize)]
+pub struct User {
+    #[serde(flatten)]
+    user_info: UserInfo,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    permissions: Option<Vec<String>>,
+    roles: Vec<String>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    app_metadata: Map<String, Value>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    user_metadata: Map<String, Value>,
+    /// Set for the users configured without permissions and roles, who are granted every permission of the
+    /// audiences until roles are assigned to them
+    #[serde(skip)]
+    all_permissions: bool,
+    #[serde(skip)]
+    blocked: bool,
+    #[serde(skip)]
+    created_at: DateTime<Utc>,
+}
+
+impl User {
+    /// A user without permissions and roles, thus granted no permission until roles are assigned to them
+    pub fn new(user_info: UserInfo) -> Self {
+        Self {
+            user_info,
+            permissions: None,
+            roles: vec![],
+            all_permissions: false,
+            app_metadata: Map::new(),
+            user_metadata: Map::new(),
+            blocked: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.user_info.sub
+    }
+
+    pub fn user_info(&self) -> &UserInfo {
+        &self.user_info
+    }
+
+    pub fn user_info_mut(&mut self) -> &mut UserInfo {
+        &mut self.user_info
+    }
+
+    pub fn app_metadata(&self) -> &Map<String, Value> {
+        &self.app_metadata
+    }
+
+    pub fn app_metadata_mut(&mut self) -> &mut Map<String, Value> {
+        &mut self.app_metadata
+    }
+
+    pub fn user_metadata(&self) -> &Map<String, Value> {
+        &self.user_metadata
+    }
+
+    pub fn user_metadata_mut(&mut self) -> &mut Map<String, Value> {
+        &mut self.user_metadata
+    }
+
+    pub fn blocked(&self) -> bool {
+        self.blocked
+    }
+
+    pub fn set_blocked(&mut self, blocked: bool) {
+        self.blocked = blocked;
+    }
+
+    pub fn created_at(&self) -> &DateTime<Utc> {
+        &self.created_at
+    }
+
+    pub fn roles(&self) -> &[String] {
+        &self.roles
+    }
+
+    /// Once roles are managed the user is granted the permissions of the roles only
+    pub fn set_roles(&mut self, roles: Vec<String>) {
+        self.roles = roles;
+        self.all_permissions = false;
+    }
+
+    /// Adds the given roles, skipping the ones already assigned
+    pub fn assign_roles(&mut self, roles: Vec<String>) {
+        let mut assigned: Vec<String> = self.roles.clone();
+        for role in roles {
+            if !assigned.contains(&role) {
+                assigned.push(role);
+            }
+        }
+        self.set_roles(assigned);
+    }
+
+    pub fn remove_roles(&mut self, roles: &[String]) {
+        let assigned: Vec<String> = self
+            .roles
+            .iter()
+            .filter(|role| !roles.contains(role))
+            .cloned()
+            .collect();
+        self.set_roles(assigned);
+    }
+
+    /// Permissions granted to the user on the given audience: the union of the user permissions belonging to the
+    /// audience and of the permissions the user roles grant on it, restricted to the ones the audience defines. A user
+    /// configured with neither permissions nor roles is granted every permission of the audience.
+    pub fn granted_permissions(
+        &self,
+        audience: &str,
+        audience_permissions: Vec<String>,
+        roles: &[Role],
+    ) -> Vec<String> {
+        if self.all_permissions {
+            return audience_permissions;
+        }
+
+        let user_permissions: &[String] = self.permissions.as_deref().unwrap_or_default();
+        let mut permissions: Vec<String> = audience_permissions
+            .iter()
+            .filter(|permission| user_permissions.contains(permission))
+            .cloned()
+            .collect();
+
+        let role_permissions = roles
+            .iter()
+            .filter(|role| self.roles.iter().any(|name| name == role.name()))
+            .flat_map(|role| role.permissions_for(audience))
+            .filter(|permission| audience_permissions.contains(permission));
+
+        for permission in role_permissions {
+            if !permissions.contains(permission) {
+                permissions.push(permission.clone());
+            }
+        }
+
+        permissions
+    }
+}
+
+impl From<&UserConfig> for User {
+    fn from(value: &UserConfig) -> Self {
+        Self {
+            user_info: UserInfo {
+                sub: value.subject().clone().unwrap_or_else(|| value.name().to_string()),
+                name: value.name().to_string(),
+                given_name: value.given_name().to_string(),
+                family_name: value.family_name().to_string(),
+                nickname: value.nickname().to_string(),
+                locale: value.locale().to_string(),
+                gender: value.gender().to_string(),
+                birthdate: value.birthdate().to_string(),
+                email: value.email().to_string(),
+                email_verified: *value.email_verified(),
+                picture: value.picture().to_string(),
+                updated_at: *value.updated_at(),
+                custom_fields: value.custom_fields().clone(),
+            },
+            permissions: value.permissions().clone(),
+            roles: value.roles().clone(),
+            all_permissions: value.permissions().is_none() && value.roles().is_empty(),
+            app_metadata: value.app_metadata().clone(),
+            user_metadata: value.user_metadata().clone(),
+            blocked: false,
+            created_at: *value.updated_at(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::Config;
+    use crate::model::{Role, User};
+
+    #[test]
+    fn user_permissions_are_restricted_to_the_configured_ones() {
+        let config_str: &str = r#"
+        [[user]]
+        name = "admin"
+
+        [[user]]
+        subject = "auth0|reader"
+        name = "reader"
+        permissions = ["audience1:read", "audience2:read"]
+        "#;
+
+        let config: Config = toml::from_str(config_str).unwrap();
+        let audience_permissions = vec!["audience1:read".to_string(), "audience1:write".to_string()];
+
+        let admin: User = (&config.user()[0]).into();
+        assert_eq!(admin.subject(), "admin");
+        assert_eq!(
+            admin.granted_permissions("audience1", audience_permissions.clone(), &[]),
+            ["audience1:read", "audience1:write"]
+        );
+
+        let reader: User = (&config.user()[1]).into();
+        assert_eq!(reader.subject(), "auth0|reader");
+        assert_eq!(
+            reader.granted_permissions("audience1", audience_permissions, &[]),
+            ["audience1:read"]
+        );
+    }
+
+    #[test]
+    fn user_permissions_include_the_ones_granted_by_roles() {
+        let config_str: &str = r#"
+        [[role]]
+        name = "writer"
+        permissions = { audience1 = ["audience1:write"], audience2 = ["audience2:write"] }
+
+        [[role]]
+        name = "deleter"
+        permissions = { audience1 = ["audience1:delete"] }
+
+        [[user]]
+        name = "writer"
+        permissions = ["audience1:read"]
+        roles = ["writer", "unknown"]
+
+        [[user]]
+        name = "guest"
+        roles = ["unknown"]
+        "#;
+
+        let config: Config = toml::from_str(config_str).unwrap();
+        let roles: Vec<Role> = config.role().iter().map(Role::from).collect();
+        let audience_permissions = vec!["audience1:read".to_string(), "audience1:write".to_string()];
+
+        let writer: User = (&config.user()[0]).into();
+        assert_eq!(
+            writer.granted_permissions("audience1", audience_permissions.clone(), &roles),
+            ["audience1:read", "audience1:write"]
+        );
+        assert_eq!(
+            writer.granted_permissions("audience2", vec!["audience2:write".to_string()], &roles),
+            ["audience2:write"]
+        );
+        // Roles can't grant permissions the audience doesn't define
+        assert!(writer.granted_permissions("audience2", vec![], &roles).is_empty());
+
+        let guest: User = (&config.user()[1]).into();
+        assert!(guest
+            .granted_permissions("audience1", audience_permissions.clone(), &roles)
+            .is_empty());
+
+        // Removing the last role doesn't grant every permission
+        let mut writer: User = writer.clone();
+        writer.set_roles(vec![]);
+        assert_eq!(
+            writer.granted_permissions("audience1", audience_permissions, &roles),
+            ["audience1:read"]
+        );
+    }
+
+    #[test]
+    fn assigned_roles_are_deduplicated() {
+        let config: Config = toml::from_str("[[user]]\nname = \"user\"\nroles = [\"reader\"]").unwrap();
+        let mut user: User = (&config.user()[0]).into();
+        user.assign_roles(vec!["writer".to_string(), "reader".to_string(), "writer".to_string()]);
+        user.assign_roles(vec!["writer".to_string(), "admin".to_string()]);
+        assert_eq!(user.roles(), ["reader", "writer", "admin"]);
+
+        user.remove_roles(&["writer".to_string(), "unknown".to_string()]);
+        assert_eq!(user.roles(), ["reader", "admin"]);
+    }
+}
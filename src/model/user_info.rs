@@ -1,8 +1,17 @@
 use chrono::{DateTime, SecondsFormat, Utc};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
 
-use crate::config::{CustomField, CustomFieldValue, UserInfoConfig};
+use crate::config::{CustomField, UserInfoConfig};
+use crate::error::Error;
+
+/// Scopes defined by <https://openid.net/specs/openid-connect-core-1_0.html#ScopeClaims>
+pub const OPENID_SCOPES: [&str; 5] = ["openid", "profile", "email", "address", "phone"];
+
+const PROFILE_SCOPE: &str = "profile";
+const EMAIL_SCOPE: &str = "email";
+const EMAIL_CLAIMS: [&str; 2] = ["email", "email_verified"];
 
 #[derive(Debug, Clone)]
 #[cfg_attr(test, derive(serde::Deserialize, PartialEq))]
@@ -42,6 +51,30 @@ impl From<&UserInfoConfig> for UserInfo {
     }
 }
 
+impl UserInfo {
+    /// Claims returned by the userinfo endpoint for a token granted the given scopes. `sub` is always returned,
+    /// email claims require the `email` scope and every other claim requires the `profile` scope.
+    pub fn claims_for_scope(&self, scope: &str) -> Result<Map<String, Value>, Error> {
+        let scopes: Vec<&str> = scope.split_whitespace().collect();
+
+        let mut claims: Map<String, Value> = match serde_json::to_value(self)? {
+            Value::Object(claims) => claims,
+            _ => Map::new(),
+        };
+
+        claims.retain(|name, _| {
+            name == "sub"
+                || if EMAIL_CLAIMS.contains(&name.as_str()) {
+                    scopes.contains(&EMAIL_SCOPE)
+                } else {
+                    scopes.contains(&PROFILE_SCOPE)
+                }
+        });
+
+        Ok(claims)
+    }
+}
+
 impl Serialize for UserInfo {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -68,10 +101,7 @@ impl Serialize for UserInfo {
         )?;
 
         for custom_field in self.custom_fields.as_deref().unwrap_or_default() {
-            match custom_field.value() {
-                CustomFieldValue::String(string) => map.serialize_entry(custom_field.name(), &string),
-                CustomFieldValue::Vec(vec) => map.serialize_entry(custom_field.name(), &vec),
-            }?;
+            map.serialize_entry(custom_field.name(), &custom_field.value().to_json())?;
         }
 
         map.end()
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::error::Error;
+use crate::model::{defaults, ClaimsPatch};
+
+/// Service called before signing the tokens. It receives the proposed claims along with the event given to the
+/// actions and answers with the changes to the custom claims, in the same shape of the action ones, or with an
+/// oauth error to deny access.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    url: String,
+    /// Timeout in milliseconds
+    #[serde(default = "defaults::webhook_timeout")]
+    timeout: u64,
+    /// When enabled tokens are issued unchanged if the webhook can't be reached or answers with anything but claims or
+    /// a denial
+    #[serde(default)]
+    fail_open: bool,
+}
+
+/// Error body of a webhook denying access, as in <https://datatracker.ietf.org/doc/html/rfc6749#section-5.2>
+#[derive(Deserialize)]
+struct Denial {
+    error: String,
+    error_description: Option<String>,
+}
+
+impl Webhook {
+    pub async fn call(&self, client: &reqwest::Client, body: &Value) -> Result<ClaimsPatch, Error> {
+        match self.send(client, body).await {
+            Ok(Ok(patch)) => Ok(patch),
+            Ok(Err(denial)) => Err(denial),
+            Err(reason) if self.fail_open => {
+                tracing::warn!("Ignoring the failure of webhook '{}': {}", self.url, reason);
+                Ok(ClaimsPatch::default())
+            }
+            Err(reason) => Err(Error::WebhookFailed(format!(
+                "Webhook '{}' failed: {}",
+                self.url, reason
+            ))),
+        }
+    }
+
+    /// Outer error is a failure of the webhook, inner one a denial
+    async fn send(&self, client: &reqwest::Client, body: &Value) -> Result<Result<ClaimsPatch, Error>, String> {
+        let response = client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body.to_string())
+            .timeout(Duration::from_millis(self.timeout))
+            .send()
+            .await
+            .map_err(|err| err.to_string())?;
+
+        let status = response.status();
+        let bytes = response.bytes().await.map_err(|err| err.to_string())?;
+
+        // Only an oauth error body denies access, other errors are failures of the webhook
+        if matches!(
+            status,
+            StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            if let Ok(denial) = serde_json::from_slice::<Denial>(&bytes) {
+                let description: String = denial
+                    .error_description
+                    .unwrap_or_else(|| "Access denied by webhook".to_string());
+
+                return Ok(Err(Error::denied(&denial.error, description)));
+            }
+        }
+
+        if !status.is_success() {
+            return Err(format!("unexpected status {status}"));
+        }
+
+        if bytes.is_empty() {
+            return Ok(Ok(ClaimsPatch::default()));
+        }
+
+        serde_json::from_slice(&bytes)
+            .map(Ok)
+            .map_err(|err| format!("invalid response: {err}"))
+    }
+}
@@ -0,0 +1,304 @@
+use std::sync::{Arc, Mutex, RwLock};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Position, Scope, AST};
+use serde_json::{Map, Value};
+
+use crate::config::ActionConfig;
+use crate::error::Error;
+use crate::model::{Action, ClaimsPatch, Trigger};
+
+/// Upper bound of the operations run by a single script, so that a looping action can't hang the server
+const MAX_OPERATIONS: u64 = 100_000;
+
+pub struct Actions {
+    engine: Engine,
+    cache: RwLock<Vec<(Action, AST)>>,
+}
+
+impl Actions {
+    pub fn new(actions: &[ActionConfig]) -> Result<Self, Error> {
+        let engine: Engine = new_engine();
+
+        let actions: Vec<(Action, AST)> = actions
+            .iter()
+            .map(|action| {
+                let action: Action = action.into();
+                let ast: AST = compile(&engine, &action)?;
+                Ok((action, ast))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(Self {
+            engine,
+            cache: RwLock::new(actions),
+        })
+    }
+
+    pub fn all(&self) -> Result<Vec<Action>, Error> {
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .map(|(action, _)| action.clone())
+            .collect())
+    }
+
+    /// Replaces the action with the same name, if any, otherwise appends it to the ones run by its trigger
+    pub fn put(&self, action: Action) -> Result<(), Error> {
+        let ast: AST = compile(&self.engine, &action)?;
+        let mut lock = self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        match lock.iter_mut().find(|(existing, _)| existing.name() == action.name()) {
+            Some(entry) => *entry = (action, ast),
+            None => lock.push((action, ast)),
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<Option<Action>, Error> {
+        let mut lock = self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        Ok(lock
+            .iter()
+            .position(|(action, _)| action.name() == name)
+            .map(|index| lock.remove(index).0))
+    }
+
+    /// Runs, in order, the actions bound to the trigger and collects the changes they make to the claims.
+    /// The first action denying access stops the pipeline.
+    pub fn execute(&self, trigger: Trigger, event: &Value) -> Result<ClaimsPatch, Error> {
+        let event: Dynamic = rhai::serde::to_dynamic(event).map_err(|err| Error::ActionFailed(err.to_string()))?;
+        let mut patch: ClaimsPatch = ClaimsPatch::default();
+
+        for (action, ast) in self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .filter(|(action, _)| action.trigger() == trigger)
+        {
+            let api: Api = Api::default();
+            let mut scope: Scope = Scope::new();
+            scope.push_constant("event", event.clone());
+            scope.push_constant("api", api.clone());
+
+            self.engine
+                .run_ast_with_scope(&mut scope, ast)
+                .map_err(|err| Error::ActionFailed(format!("Action '{}' failed: {}", action.name(), err)))?;
+
+            if let Some(denial) = api.access.denial.lock().unwrap_or_else(|p| p.into_inner()).take() {
+                return Err(Error::denied(&denial.code, denial.reason));
+            }
+
+            patch.merge(ClaimsPatch {
+                access_token: api.access_token.take(),
+                id_token: api.id_token.take(),
+            });
+        }
+
+        Ok(patch)
+    }
+}
+
+/// The `api` object given to the scripts
+#[derive(Clone, Default)]
+struct Api {
+    access_token: TokenApi,
+    id_token: TokenApi,
+    access: AccessApi,
+}
+
+/// `api.accessToken` and `api.idToken`
+#[derive(Clone, Default)]
+struct TokenApi {
+    claims: Arc<Mutex<Map<String, Value>>>,
+}
+
+impl TokenApi {
+    fn set_custom_claim(&mut self, name: &str, value: Dynamic) -> Result<(), Box<EvalAltResult>> {
+        let value: Value = rhai::serde::from_dynamic(&value)?;
+        self.claims
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(name.to_string(), value);
+
+        Ok(())
+    }
+
+    fn remove_custom_claim(&mut self, name: &str) {
+        self.claims
+            .lock()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(name.to_string(), Value::Null);
+    }
+
+    fn take(&self) -> Map<String, Value> {
+        std::mem::take(&mut *self.claims.lock().unwrap_or_else(|p| p.into_inner()))
+    }
+}
+
+/// `api.access`
+#[derive(Clone, Default)]
+struct AccessApi {
+    denial: Arc<Mutex<Option<Denial>>>,
+}
+
+impl AccessApi {
+    fn deny(&mut self, code: &str, reason: &str) {
+        *self.denial.lock().unwrap_or_else(|p| p.into_inner()) = Some(Denial {
+            code: code.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+}
+
+struct Denial {
+    code: String,
+    reason: String,
+}
+
+fn new_engine() -> Engine {
+    let mut engine: Engine = Engine::new();
+
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(64 * 1024)
+        .set_max_array_size(10_000)
+        .set_max_map_size(10_000)
+        .on_print(|text| tracing::info!("{}", text))
+        .on_debug(|text, _, position: Position| tracing::debug!("{} ({})", text, position));
+
+    engine
+        .register_type_with_name::<Api>("Api")
+        .register_get("accessToken", |api: &mut Api| api.access_token.clone())
+        .register_get("idToken", |api: &mut Api| api.id_token.clone())
+        .register_get("access", |api: &mut Api| api.access.clone())
+        .register_type_with_name::<TokenApi>("TokenApi")
+        .register_fn("setCustomClaim", TokenApi::set_custom_claim)
+        .register_fn("removeCustomClaim", TokenApi::remove_custom_claim)
+        .register_type_with_name::<AccessApi>("AccessApi")
+        .register_fn("deny", |access: &mut AccessApi, reason: &str| {
+            access.deny("access_denied", reason)
+        })
+        .register_fn("deny", AccessApi::deny);
+
+    engine
+}
+
+fn compile(engine: &Engine, action: &Action) -> Result<AST, Error> {
+    engine
+        .compile(action.script())
+        .map_err(|err| Error::InvalidRequest(format!("Invalid script for action '{}': {}", action.name(), err)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::Actions;
+    use crate::config::ActionConfig;
+    use crate::error::Error;
+    use crate::model::{Action, Trigger};
+
+    fn action(name: &str, trigger: Trigger, script: &str) -> Action {
+        serde_json::from_value(json!({ "name": name, "trigger": trigger, "script": script })).unwrap()
+    }
+
+    #[test]
+    fn execute_test() {
+        let actions: Actions = Actions::new(&[]).unwrap();
+        actions
+            .put(action(
+                "tenant",
+                Trigger::PostLogin,
+                r#"
+                    api.accessToken.setCustomClaim("tenant", event.user.app_metadata.tenant);
+                    api.idToken.setCustomClaim("roles", event.authorization.roles);
+                    api.idToken.removeCustomClaim("name");
+                "#,
+            ))
+            .unwrap();
+        actions
+            .put(action(
+                "m2m",
+                Trigger::CredentialsExchange,
+                r#"api.accessToken.setCustomClaim("m2m", true);"#,
+            ))
+            .unwrap();
+
+        let event = json!({
+            "user": { "app_metadata": { "tenant": "acme" } },
+            "authorization": { "roles": ["admin"] },
+        });
+        let patch = actions.execute(Trigger::PostLogin, &event).unwrap();
+
+        assert_eq!(
+            patch.access_token,
+            json!({ "tenant": "acme" }).as_object().unwrap().clone()
+        );
+        assert_eq!(
+            patch.id_token,
+            json!({ "roles": ["admin"], "name": null }).as_object().unwrap().clone()
+        );
+
+        let patch = actions.execute(Trigger::CredentialsExchange, &event).unwrap();
+        assert_eq!(patch.access_token, json!({ "m2m": true }).as_object().unwrap().clone());
+        assert!(patch.id_token.is_empty());
+    }
+
+    #[test]
+    fn deny_and_failures_test() {
+        let actions: Actions = Actions::new(&[]).unwrap();
+        actions
+            .put(action(
+                "deny",
+                Trigger::PostLogin,
+                r#"if event.user.blocked { api.access.deny("invalid_scope", "blocked user"); }"#,
+            ))
+            .unwrap();
+
+        assert!(actions
+            .execute(Trigger::PostLogin, &json!({ "user": { "blocked": false } }))
+            .is_ok());
+        assert!(matches!(
+            actions.execute(Trigger::PostLogin, &json!({ "user": { "blocked": true } })),
+            Err(Error::InvalidScope(reason)) if reason == "blocked user"
+        ));
+
+        actions
+            .put(action("deny", Trigger::PostLogin, r#"throw "boom";"#))
+            .unwrap();
+        assert_eq!(actions.all().unwrap().len(), 1);
+        assert!(matches!(
+            actions.execute(Trigger::PostLogin, &json!({})),
+            Err(Error::ActionFailed(_))
+        ));
+
+        actions.put(action("deny", Trigger::PostLogin, "loop {}")).unwrap();
+        assert!(matches!(
+            actions.execute(Trigger::PostLogin, &json!({})),
+            Err(Error::ActionFailed(_))
+        ));
+
+        assert!(actions.remove("deny").unwrap().is_some());
+        assert!(actions.execute(Trigger::PostLogin, &json!({})).is_ok());
+
+        assert!(matches!(
+            actions.put(action("broken", Trigger::PostLogin, "let = ;")),
+            Err(Error::InvalidRequest(_))
+        ));
+        let config: ActionConfig = toml::from_str(
+            r#"
+                name = "broken"
+                trigger = "post-login"
+                script = "let = ;"
+            "#,
+        )
+        .unwrap();
+        assert!(Actions::new(&[config]).is_err());
+    }
+}
@@ -2,18 +2,21 @@ use crate::config::AudienceConfig;
 use std::collections::HashMap;
 use std::sync::RwLock;
 
+use jsonwebtoken::Algorithm;
+
 use crate::error::Error;
+use crate::model::Audience;
 
 pub struct Audiences {
-    cache: RwLock<HashMap<String, Vec<String>>>,
+    cache: RwLock<HashMap<String, Audience>>,
 }
 
 impl Audiences {
     pub fn new(audiences: &[AudienceConfig]) -> Self {
-        let mut map: HashMap<String, Vec<String>> = HashMap::new();
+        let mut map: HashMap<String, Audience> = HashMap::new();
 
         for audience in audiences {
-            map.insert(audience.name().to_string(), audience.permissions().clone());
+            map.insert(audience.name().to_string(), Audience::from(audience));
         }
 
         Self {
@@ -21,26 +24,76 @@ impl Audiences {
         }
     }
 
+    pub fn get(&self, audience: &str) -> Result<Option<Audience>, Error> {
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .get(audience)
+            .cloned())
+    }
+
+    pub fn put(&self, audience: Audience) -> Result<(), Error> {
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(audience.identifier().to_string(), audience);
+
+        Ok(())
+    }
+
     pub fn get_permissions(&self, audience: &str) -> Result<Vec<String>, Error> {
         Ok(self
             .cache
             .read()
             .unwrap_or_else(|p| p.into_inner())
             .get(audience)
-            .cloned()
+            .map(|audience| audience.permissions().clone())
             .unwrap_or_default())
     }
 
-    pub fn put_permissions(&self, audience: &str, permissions: Vec<String>) -> Result<(), Error> {
+    /// Unknown audiences are created with the default settings, signing with the given algorithm
+    pub fn put_permissions(
+        &self,
+        audience: &str,
+        permissions: Vec<String>,
+        default_signing_alg: Algorithm,
+    ) -> Result<(), Error> {
         self.cache
             .write()
             .unwrap_or_else(|p| p.into_inner())
-            .insert(audience.to_string(), permissions);
+            .entry(audience.to_string())
+            .or_insert_with(|| Audience::new(audience.to_string(), vec![], default_signing_alg))
+            .set_permissions(permissions);
 
         Ok(())
     }
 
+    pub fn remove(&self, audience: &str) -> Result<Option<Audience>, Error> {
+        Ok(self.cache.write().unwrap_or_else(|p| p.into_inner()).remove(audience))
+    }
+
+    /// Permissions by audience
     pub fn all(&self) -> Result<HashMap<String, Vec<String>>, Error> {
-        Ok(self.cache.read().unwrap_or_else(|p| p.into_inner()).clone())
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .iter()
+            .map(|(name, audience)| (name.clone(), audience.permissions().clone()))
+            .collect())
+    }
+
+    /// Audiences sorted by identifier
+    pub fn list(&self) -> Result<Vec<Audience>, Error> {
+        let mut audiences: Vec<Audience> = self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .cloned()
+            .collect();
+        audiences.sort_by(|a, b| a.identifier().cmp(b.identifier()));
+        Ok(audiences)
     }
 }
@@ -1,41 +1,103 @@
 use std::collections::HashMap;
 use std::sync::RwLock;
 
+use chrono::{Duration, Utc};
+
+use crate::config::AuthorizationCodeConfig;
 use crate::error::Error;
+use crate::model::Authorization;
 
 pub struct Authorizations {
-    cache: RwLock<HashMap<String, String>>,
+    cache: RwLock<HashMap<String, Authorization>>,
+    ttl: Duration,
 }
 
-impl Default for Authorizations {
-    fn default() -> Self {
+impl Authorizations {
+    pub fn new(config: &AuthorizationCodeConfig) -> Self {
         Self {
             cache: RwLock::new(HashMap::new()),
+            ttl: Duration::seconds(*config.ttl()),
         }
     }
-}
 
-impl Authorizations {
-    pub fn get_audience_for_authorization(&self, code: &str) -> Result<String, Error> {
-        Ok(self
-            .cache
-            .read()
+    /// Authorization codes are single use: the authorization is removed from the store as soon as it's taken
+    pub fn take_authorization(&self, code: &str) -> Result<Authorization, Error> {
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .remove(code)
+            .filter(|authorization| !self.is_expired(authorization))
+            .ok_or_else(|| Error::InvalidGrant("Invalid authorization code".to_string()))
+    }
+
+    pub fn put_authorization(&self, code: &str, authorization: Authorization) -> Result<(), Error> {
+        self.cache
+            .write()
             .unwrap_or_else(|p| p.into_inner())
-            .get(code)
-            .cloned()
-            .unwrap_or_default())
+            .insert(code.to_string(), authorization);
+
+        Ok(())
     }
 
-    pub fn put_authorization(&self, code: &str, audience: String) -> Result<(), Error> {
+    pub fn purge_expired(&self) -> Result<(), Error> {
         self.cache
             .write()
             .unwrap_or_else(|p| p.into_inner())
-            .insert(code.to_string(), audience);
+            .retain(|_, authorization| !self.is_expired(authorization));
 
         Ok(())
     }
 
-    pub fn all(&self) -> Result<HashMap<String, String>, Error> {
+    pub fn all(&self) -> Result<HashMap<String, Authorization>, Error> {
         Ok(self.cache.read().unwrap_or_else(|p| p.into_inner()).clone())
     }
+
+    fn is_expired(&self, authorization: &Authorization) -> bool {
+        Utc::now() - authorization.created_at > self.ttl
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use crate::config::AuthorizationCodeConfig;
+    use crate::error::Error;
+    use crate::model::Authorization;
+    use crate::store::AuthorizationsStore;
+
+    fn authorization() -> Authorization {
+        Authorization {
+            audience: "audience".to_string(),
+            client_id: None,
+            redirect_uri: None,
+            scope: None,
+            nonce: None,
+            code_challenge: None,
+            subject: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn authorization_code_is_single_use() {
+        let store = AuthorizationsStore::new(&AuthorizationCodeConfig::default());
+        store.put_authorization("code", authorization()).unwrap();
+
+        assert_eq!(store.take_authorization("code").unwrap().audience, "audience");
+        assert!(matches!(store.take_authorization("code"), Err(Error::InvalidGrant(_))));
+    }
+
+    #[test]
+    fn expired_authorization_codes_are_rejected_and_purged() {
+        let config: AuthorizationCodeConfig = toml::from_str("ttl = -1").unwrap();
+        let store = AuthorizationsStore::new(&config);
+        store.put_authorization("code1", authorization()).unwrap();
+        store.put_authorization("code2", authorization()).unwrap();
+
+        assert!(matches!(store.take_authorization("code1"), Err(Error::InvalidGrant(_))));
+
+        store.purge_expired().unwrap();
+        assert!(store.all().unwrap().is_empty());
+    }
 }
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::config::ClientConfig;
+use crate::error::Error;
+use crate::model::Client;
+
+pub struct Clients {
+    cache: RwLock<HashMap<String, Client>>,
+}
+
+impl Clients {
+    /// The default `client_id`/`client_secret` client is always registered, unless a configured client has the same
+    /// id, since the web frontend requests its tokens with it.
+    pub fn new(clients: &[ClientConfig]) -> Self {
+        let map: HashMap<String, Client> = std::iter::once(&ClientConfig::default())
+            .chain(clients)
+            .map(|client| (client.id().to_string(), client.into()))
+            .collect();
+
+        Self {
+            cache: RwLock::new(map),
+        }
+    }
+
+    pub fn get(&self, client_id: &str) -> Result<Option<Client>, Error> {
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .get(client_id)
+            .cloned())
+    }
+
+    pub fn has_public_client(&self) -> Result<bool, Error> {
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .any(Client::is_public))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Clients;
+    use crate::config::Config;
+    use crate::{CLIENT_ID_VALUE, CLIENT_SECRET_VALUE};
+
+    #[test]
+    fn default_client_is_registered_along_with_the_configured_ones_test() {
+        let config: Config = toml::from_str(
+            r#"
+            [[client]]
+            id = "backend"
+            secret = "backend_secret"
+            "#,
+        )
+        .unwrap();
+        let clients: Clients = Clients::new(config.client());
+
+        assert!(clients.get("backend").unwrap().unwrap().verify_secret("backend_secret"));
+        assert!(clients
+            .get(CLIENT_ID_VALUE)
+            .unwrap()
+            .unwrap()
+            .verify_secret(CLIENT_SECRET_VALUE));
+
+        let config: Config = toml::from_str(
+            r#"
+            [[client]]
+            id = "client_id"
+            secret = "overridden_secret"
+            "#,
+        )
+        .unwrap();
+        let clients: Clients = Clients::new(config.client());
+        assert!(clients
+            .get(CLIENT_ID_VALUE)
+            .unwrap()
+            .unwrap()
+            .verify_secret("overridden_secret"));
+    }
+}
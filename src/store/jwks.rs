@@ -1,51 +1,217 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::{RwLock, RwLockWriteGuard};
+use std::time::Duration;
 
+use chrono::{TimeDelta, Utc};
+use jsonwebtoken::Algorithm;
+use serde::{Deserialize, Serialize};
+
+use crate::config::JwksConfig;
 use crate::error::Error;
-use crate::model::{Jwk, Jwks};
+use crate::model::{Jwk, Jwks, KeyLifecycle, KidFormat, PrivateJwk};
+
+/// Name of the file the keys are persisted to, inside the configured directory
+const JWKS_FILE_NAME: &str = "jwks.json";
 
 pub struct JwksStore {
     cache: RwLock<Jwks>,
+    /// Algorithms keys are generated for. The first one signs the id tokens
+    signing_algs: Vec<Algorithm>,
+    kid_format: KidFormat,
+    directory: Option<PathBuf>,
+}
+
+/// Private keys as written to disk, along with their lifecycle
+#[derive(Serialize, Deserialize)]
+struct PersistedJwks {
+    keys: Vec<PersistedJwk>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedJwk {
+    #[serde(flatten)]
+    jwk: PrivateJwk,
+    #[serde(flatten)]
+    lifecycle: KeyLifecycle,
 }
 
 impl JwksStore {
-    pub fn new() -> Result<Self, Error> {
-        Ok(Self {
-            cache: RwLock::new(Jwks::new()?),
-        })
+    /// Loads the persisted keys and the configured ones, the latter replacing persisted keys with the same `kid` while
+    /// keeping their lifecycle, then generates the keys missing to every algorithm
+    pub fn new(config: &JwksConfig, signing_algs: Vec<Algorithm>) -> Result<Self, Error> {
+        let mut keys: Vec<Jwk> = match config.directory() {
+            Some(directory) => load(directory)?,
+            None => vec![],
+        };
+
+        for key in config.key() {
+            let mut jwk: Jwk = key.try_into()?;
+            match keys.iter_mut().find(|existing| existing.kid() == jwk.kid()) {
+                Some(existing) => {
+                    jwk.set_lifecycle(existing.lifecycle().clone());
+                    *existing = jwk;
+                }
+                None => keys.push(jwk),
+            }
+        }
+
+        let store: Self = Self {
+            cache: RwLock::new(Jwks { keys }.with_missing_keys(&signing_algs, *config.kid_format())?),
+            signing_algs,
+            kid_format: *config.kid_format(),
+            directory: config.directory().clone(),
+        };
+        store.persist(&store.cache.read().unwrap_or_else(|p| p.into_inner()))?;
+
+        Ok(store)
+    }
+
+    pub fn default_signing_alg(&self) -> Algorithm {
+        self.signing_algs.first().copied().unwrap_or(Algorithm::RS256)
     }
 
+    /// Published keys, the ones tokens are verified with
     pub fn get(&self) -> Result<Jwks, Error> {
-        Ok(self.cache.read().unwrap_or_else(|p| p.into_inner()).clone())
+        Ok(self.cache.read().unwrap_or_else(|p| p.into_inner()).published())
+    }
+
+    /// Every key, revoked ones included
+    pub fn all(&self) -> Result<Vec<Jwk>, Error> {
+        let mut keys: Vec<Jwk> = self.cache.read().unwrap_or_else(|p| p.into_inner()).keys.clone();
+        keys.sort_by_key(|jwk| jwk.lifecycle().state.rank());
+        Ok(keys)
+    }
+
+    /// The current key of the default algorithm
+    pub fn signing_jwk(&self) -> Result<Jwk, Error> {
+        self.signing_jwk_for(self.default_signing_alg())
     }
 
-    pub fn random_jwk(&self) -> Result<Jwk, Error> {
-        self.cache.read().unwrap_or_else(|p| p.into_inner()).random_jwk()
+    pub fn signing_jwk_for(&self, alg: Algorithm) -> Result<Jwk, Error> {
+        self.cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .current_jwk_for(alg)
     }
 
     pub fn rotate_keys(&self) -> Result<(), Error> {
         let mut jwks: RwLockWriteGuard<Jwks> = self.cache.write().unwrap_or_else(|p| p.into_inner());
-        *jwks = jwks.rotate_keys()?;
-        Ok(())
+        *jwks = jwks.rotate_keys(&self.signing_algs, self.kid_format, Utc::now())?;
+        self.persist(&jwks)
+    }
+
+    /// Retires the previous keys once published for the given overlap since the rotation
+    pub fn retire_previous_keys(&self, overlap: Duration) -> Result<(), Error> {
+        let Some(current_until) = TimeDelta::from_std(overlap)
+            .ok()
+            .and_then(|overlap| Utc::now().checked_sub_signed(overlap))
+        else {
+            return Ok(());
+        };
+
+        let mut jwks: RwLockWriteGuard<Jwks> = self.cache.write().unwrap_or_else(|p| p.into_inner());
+        *jwks = jwks.retire_previous_keys(current_until);
+        self.persist(&jwks)
+    }
+
+    pub fn revoke_key(&self, kid: &str) -> Result<Jwk, Error> {
+        let mut jwks: RwLockWriteGuard<Jwks> = self.cache.write().unwrap_or_else(|p| p.into_inner());
+        *jwks = jwks.revoke_key(kid, &self.signing_algs, self.kid_format)?;
+        self.persist(&jwks)?;
+
+        jwks.find(kid.to_string())
+            .ok_or_else(|| Error::NotFound(format!("Signing key '{kid}' not found")))
     }
 
     pub fn revoke_keys(&self) -> Result<(), Error> {
         let mut jwks: RwLockWriteGuard<Jwks> = self.cache.write().unwrap_or_else(|p| p.into_inner());
-        *jwks = jwks.revoke_keys()?;
+        *jwks = jwks.revoke_keys(&self.signing_algs, self.kid_format)?;
+        self.persist(&jwks)
+    }
+
+    /// Writes the keys to a temporary file renamed afterwards, so that a crash never leaves a truncated file behind
+    fn persist(&self, jwks: &Jwks) -> Result<(), Error> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+
+        let persisted: PersistedJwks = PersistedJwks {
+            keys: jwks
+                .keys
+                .iter()
+                .map(|jwk| {
+                    Ok(PersistedJwk {
+                        jwk: jwk.to_private_jwk()?,
+                        lifecycle: jwk.lifecycle().clone(),
+                    })
+                })
+                .collect::<Result<_, Error>>()?,
+        };
+        let path: PathBuf = directory.join(JWKS_FILE_NAME);
+        let temp_path: PathBuf = directory.join(format!("{JWKS_FILE_NAME}.tmp"));
+
+        fs::create_dir_all(directory)?;
+        write_private(&temp_path, &serde_json::to_vec_pretty(&persisted)?)?;
+        fs::rename(temp_path, path)?;
+
         Ok(())
     }
 }
 
+/// Writes a file holding private keys, readable by the owner only on unix
+fn write_private(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    // A leftover file would keep its permissions
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != std::io::ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+
+    let mut options: OpenOptions = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(path)?.write_all(contents)?;
+
+    Ok(())
+}
+
+fn load(directory: &Path) -> Result<Vec<Jwk>, Error> {
+    let path: PathBuf = directory.join(JWKS_FILE_NAME);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+
+    let persisted: PersistedJwks = serde_json::from_slice(&fs::read(&path)?)?;
+    persisted
+        .keys
+        .iter()
+        .map(|persisted| {
+            let mut jwk: Jwk = Jwk::from_private_jwk(&persisted.jwk, None, None)?;
+            jwk.set_lifecycle(persisted.lifecycle.clone());
+            Ok(jwk)
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::config::CustomField;
+    use base64::prelude::BASE64_STANDARD;
+    use base64::Engine;
+    use jsonwebtoken::Algorithm;
+
+    use crate::config::{CustomField, JwksConfig};
     use crate::error::Error;
-    use crate::model::{Claims, GrantType, Jwk, Jwks};
+    use crate::model::{Claims, GrantType, Jwk, Jwks, KeyState, KidFormat};
     use crate::store::JwksStore;
+    use crate::CLIENT_ID_VALUE;
     use serde_json::json;
 
     #[test]
     fn its_possible_to_generate_jwks_and_parse_claims_using_given_jwks_test() {
-        let jwk_store: JwksStore = JwksStore::new().unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256]).unwrap();
         let audience: &str = "audience";
         let permission: &str = "permission";
         let issuer: &str = "issuer";
@@ -53,10 +219,11 @@ mod tests {
         let gty: GrantType = GrantType::ClientCredentials;
 
         let jwks: Jwks = jwk_store.get().unwrap();
-        let random_jwk: Jwk = jwks.random_jwk().unwrap();
+        let current_jwk: Jwk = jwks.current_jwk().unwrap();
 
         let claims: Claims = Claims::new(
             audience.to_string(),
+            CLIENT_ID_VALUE.to_string(),
             vec![permission.to_string()],
             issuer.to_string(),
             subject.to_string(),
@@ -64,12 +231,13 @@ mod tests {
             vec![],
         );
 
-        let jwt: String = random_jwk.encode(&claims).unwrap();
+        let jwt: String = current_jwk.encode(&claims).unwrap();
         let result: Result<Claims, Error> = jwks.parse(jwt.as_ref(), &[audience]);
         assert!(result.is_ok());
 
         let claims: Claims = result.unwrap();
         assert_eq!(claims.audience(), audience);
+        assert_eq!(claims.authorized_party(), CLIENT_ID_VALUE);
         assert!(claims.has_permission(permission));
         assert_eq!(claims.issuer(), issuer);
         assert_eq!(claims.grant_type().to_string(), gty.to_string());
@@ -77,7 +245,7 @@ mod tests {
 
     #[test]
     fn use_custom_claims_test() {
-        let jwk_store: JwksStore = JwksStore::new().unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256]).unwrap();
         let audience: &str = "audience";
         let permission: &str = "permission";
         let issuer: &str = "issuer";
@@ -85,7 +253,7 @@ mod tests {
         let gty: GrantType = GrantType::ClientCredentials;
 
         let jwks: Jwks = jwk_store.get().unwrap();
-        let random_jwk: Jwk = jwks.random_jwk().unwrap();
+        let current_jwk: Jwk = jwks.current_jwk().unwrap();
         let custom_claims: Vec<CustomField> = vec![
             serde_json::from_value(json!({ "name": "at_custom_claims_str", "value": { "String": "my_str" } })).unwrap(),
             serde_json::from_value(json!({"name": "at_custom_claims_vec", "value": {"Vec": ["foobar"]}})).unwrap(),
@@ -93,6 +261,7 @@ mod tests {
 
         let claims: Claims = Claims::new(
             audience.to_string(),
+            CLIENT_ID_VALUE.to_string(),
             vec![permission.to_string()],
             issuer.to_string(),
             subject.to_string(),
@@ -100,7 +269,7 @@ mod tests {
             custom_claims,
         );
 
-        let jwt: String = random_jwk.encode(&claims).unwrap();
+        let jwt: String = current_jwk.encode(&claims).unwrap();
         let content: serde_json::Value = jwks.parse(jwt.as_ref(), &[audience]).expect("unable to parse jwt");
         assert_eq!(content.get("at_custom_claims_str").unwrap(), "my_str");
         let custom_claim_vec: Vec<String> =
@@ -110,7 +279,7 @@ mod tests {
 
     #[test]
     fn duplicated_custom_claim_keeps_the_last_one() {
-        let jwk_store: JwksStore = JwksStore::new().unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256]).unwrap();
         let audience: &str = "audience";
         let permission: &str = "permission";
         let issuer: &str = "issuer";
@@ -118,7 +287,7 @@ mod tests {
         let gty: GrantType = GrantType::ClientCredentials;
 
         let jwks: Jwks = jwk_store.get().unwrap();
-        let random_jwk: Jwk = jwks.random_jwk().unwrap();
+        let current_jwk: Jwk = jwks.current_jwk().unwrap();
         let custom_claims: Vec<CustomField> = vec![
             serde_json::from_value(json!({ "name": "at_custom_claims_str", "value": { "String": "my-str-1" } }))
                 .unwrap(),
@@ -128,6 +297,7 @@ mod tests {
 
         let claims: Claims = Claims::new(
             audience.to_string(),
+            CLIENT_ID_VALUE.to_string(),
             vec![permission.to_string()],
             issuer.to_string(),
             subject.to_string(),
@@ -135,8 +305,412 @@ mod tests {
             custom_claims,
         );
 
-        let jwt: String = random_jwk.encode(&claims).unwrap();
+        let jwt: String = current_jwk.encode(&claims).unwrap();
         let result: serde_json::Value = jwks.parse(jwt.as_ref(), &[audience]).unwrap();
         assert_eq!(result.get("at_custom_claims_str").unwrap(), "my-str-2");
     }
+
+    #[test]
+    fn typed_custom_claims_test() {
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256]).unwrap();
+        let audience: &str = "audience";
+
+        let jwks: Jwks = jwk_store.get().unwrap();
+        let current_jwk: Jwk = jwks.current_jwk().unwrap();
+        let custom_claims: Vec<CustomField> = vec![
+            serde_json::from_value(json!({ "name": "seats", "value": { "Number": 3 } })).unwrap(),
+            serde_json::from_value(json!({ "name": "admin", "value": { "Bool": false } })).unwrap(),
+            serde_json::from_value(
+                json!({ "name": "https://example.com/tenant", "value": { "Json": { "id": "acme", "tags": [["a"]] } } }),
+            )
+            .unwrap(),
+        ];
+
+        let claims: Claims = Claims::new(
+            audience.to_string(),
+            CLIENT_ID_VALUE.to_string(),
+            vec![],
+            "issuer".to_string(),
+            "subject".to_string(),
+            GrantType::ClientCredentials,
+            custom_claims,
+        );
+
+        let jwt: String = current_jwk.encode(&claims).unwrap();
+        let content: serde_json::Value = jwks.parse(jwt.as_ref(), &[audience]).unwrap();
+        assert_eq!(content["seats"], json!(3));
+        assert_eq!(content["admin"], json!(false));
+        assert_eq!(
+            content["https://example.com/tenant"],
+            json!({ "id": "acme", "tags": [["a"]] })
+        );
+    }
+
+    #[test]
+    fn every_signing_algorithm_is_published_and_verified_test() {
+        let algs: Vec<Algorithm> = vec![
+            Algorithm::RS256,
+            Algorithm::PS256,
+            Algorithm::PS384,
+            Algorithm::PS512,
+            Algorithm::ES256,
+            Algorithm::ES384,
+            Algorithm::EdDSA,
+        ];
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), algs.clone()).unwrap();
+        let jwks: Jwks = jwk_store.get().unwrap();
+        assert_eq!(jwks.keys.len(), algs.len() * 2);
+
+        let published: serde_json::Value = serde_json::to_value(&jwks).unwrap();
+        let members = |alg: &str| -> Vec<String> {
+            let key = published["keys"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|key| key["alg"] == alg)
+                .unwrap();
+            let mut members: Vec<String> = key.as_object().unwrap().keys().cloned().collect();
+            members.retain(|member| ["kty", "crv", "n", "e", "x", "y"].contains(&member.as_str()));
+            members.push(format!(
+                "{}/{}",
+                key["kty"].as_str().unwrap(),
+                key["crv"].as_str().unwrap_or("-")
+            ));
+            members
+        };
+        assert_eq!(members("PS256"), vec!["e", "kty", "n", "RSA/-"]);
+        assert_eq!(members("ES256"), vec!["crv", "kty", "x", "y", "EC/P-256"]);
+        assert_eq!(members("ES384"), vec!["crv", "kty", "x", "y", "EC/P-384"]);
+        assert_eq!(members("EdDSA"), vec!["crv", "kty", "x", "OKP/Ed25519"]);
+
+        // Verification relies on the published members only
+        let jwks: Jwks = serde_json::from_value(published).unwrap();
+        for alg in algs {
+            let claims: Claims = Claims::new(
+                "audience".to_string(),
+                CLIENT_ID_VALUE.to_string(),
+                vec![],
+                "issuer".to_string(),
+                "subject".to_string(),
+                GrantType::ClientCredentials,
+                vec![],
+            );
+
+            let jwt: String = jwk_store.signing_jwk_for(alg).unwrap().encode(&claims).unwrap();
+            assert_eq!(jsonwebtoken::decode_header(&jwt).unwrap().alg, alg);
+            let claims: Claims = jwks.parse(&jwt, &["audience"]).unwrap();
+            assert_eq!(claims.subject(), "subject");
+        }
+
+        assert_eq!(jwk_store.signing_jwk().unwrap().alg(), "RS256");
+        assert!(JwksStore::new(&JwksConfig::default(), vec![Algorithm::HS256]).is_err());
+    }
+
+    #[test]
+    fn keys_lifecycle_test() {
+        let jwk_store: JwksStore =
+            JwksStore::new(&JwksConfig::default(), vec![Algorithm::ES256, Algorithm::EdDSA]).unwrap();
+        let states = |jwks: &Jwks| -> Vec<(String, KeyState)> {
+            jwks.keys
+                .iter()
+                .map(|jwk| (jwk.alg().to_string(), jwk.lifecycle().state))
+                .collect()
+        };
+        let kid = |jwks: &Jwks, alg: &str, state: KeyState| -> String {
+            jwks.keys
+                .iter()
+                .find(|jwk| jwk.alg() == alg && jwk.lifecycle().state == state)
+                .unwrap()
+                .kid()
+                .to_string()
+        };
+
+        let before: Jwks = jwk_store.get().unwrap();
+        assert_eq!(
+            states(&before),
+            vec![
+                ("ES256".to_string(), KeyState::Current),
+                ("EdDSA".to_string(), KeyState::Current),
+                ("ES256".to_string(), KeyState::Next),
+                ("EdDSA".to_string(), KeyState::Next),
+            ]
+        );
+        assert_eq!(
+            jwk_store.signing_jwk().unwrap().kid(),
+            kid(&before, "ES256", KeyState::Current)
+        );
+
+        // The next key becomes the current one and the current key the previous one
+        jwk_store.rotate_keys().unwrap();
+        let rotated: Jwks = jwk_store.get().unwrap();
+        assert_eq!(
+            states(&rotated)
+                .iter()
+                .map(|(_, state)| *state)
+                .collect::<Vec<KeyState>>(),
+            vec![
+                KeyState::Current,
+                KeyState::Current,
+                KeyState::Next,
+                KeyState::Next,
+                KeyState::Previous,
+                KeyState::Previous
+            ]
+        );
+        assert_eq!(
+            kid(&rotated, "ES256", KeyState::Current),
+            kid(&before, "ES256", KeyState::Next)
+        );
+        assert_eq!(
+            kid(&rotated, "ES256", KeyState::Previous),
+            kid(&before, "ES256", KeyState::Current)
+        );
+        assert_eq!(
+            jwk_store.signing_jwk().unwrap().kid(),
+            kid(&rotated, "ES256", KeyState::Current)
+        );
+
+        // Former previous keys are retired by the following rotation
+        jwk_store.rotate_keys().unwrap();
+        let retired: String = kid(&before, "ES256", KeyState::Current);
+        assert_eq!(jwk_store.get().unwrap().keys.len(), 6);
+        assert!(jwk_store.get().unwrap().find(retired).is_none());
+
+        let jwks: Jwks = jwk_store.get().unwrap();
+        assert!(matches!(
+            jwk_store.revoke_key(&kid(&jwks, "ES256", KeyState::Current)),
+            Err(Error::InvalidRequest(_))
+        ));
+        assert!(matches!(jwk_store.revoke_key("missing"), Err(Error::NotFound(_))));
+
+        let previous: String = kid(&jwks, "ES256", KeyState::Previous);
+        let revoked: Jwk = jwk_store.revoke_key(&previous).unwrap();
+        assert_eq!(revoked.lifecycle().state, KeyState::Revoked);
+        assert!(revoked.lifecycle().revoked_at.is_some());
+        assert!(jwk_store.get().unwrap().find(previous.clone()).is_none());
+        assert!(jwk_store.all().unwrap().iter().any(|jwk| jwk.kid() == previous));
+        assert!(matches!(jwk_store.revoke_key(&previous), Err(Error::Conflict(_))));
+
+        // A revoked next key is replaced
+        let next: String = kid(&jwks, "EdDSA", KeyState::Next);
+        jwk_store.revoke_key(&next).unwrap();
+        let jwks: Jwks = jwk_store.get().unwrap();
+        assert_ne!(kid(&jwks, "EdDSA", KeyState::Next), next);
+        assert_eq!(jwks.keys.len(), 5);
+    }
+
+    #[test]
+    fn previous_keys_are_retired_after_the_overlap_test() {
+        let jwk_store: JwksStore = JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256]).unwrap();
+        let current: String = jwk_store.signing_jwk().unwrap().kid().to_string();
+        jwk_store.rotate_keys().unwrap();
+
+        jwk_store
+            .retire_previous_keys(std::time::Duration::from_secs(3600))
+            .unwrap();
+        assert!(jwk_store.get().unwrap().find(current.clone()).is_some());
+
+        jwk_store.retire_previous_keys(std::time::Duration::ZERO).unwrap();
+        let jwks: Jwks = jwk_store.get().unwrap();
+        assert!(jwks.find(current).is_none());
+        assert_eq!(jwks.keys.len(), 2);
+        assert!(jwk_store.retire_previous_keys(std::time::Duration::MAX).is_ok());
+    }
+
+    #[test]
+    fn thumbprints_test() {
+        // Example of <https://datatracker.ietf.org/doc/html/rfc7638#section-3.1>
+        let jwk: Jwk = serde_json::from_value(json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29",
+            "use": "sig",
+            "x5c": [],
+        }))
+        .unwrap();
+        assert_eq!(jwk.thumbprint().unwrap(), "NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs");
+
+        let config: JwksConfig = toml::from_str(r#"kid_format = "thumbprint""#).unwrap();
+        let jwk_store: JwksStore =
+            JwksStore::new(&config, vec![Algorithm::RS256, Algorithm::ES384, Algorithm::EdDSA]).unwrap();
+        let jwks: Jwks = jwk_store.get().unwrap();
+        assert!(jwks.keys.iter().all(|jwk| jwk.kid() == jwk.thumbprint().unwrap()));
+
+        let published: serde_json::Value = serde_json::to_value(&jwks).unwrap();
+        for key in published["keys"].as_array().unwrap() {
+            let der: Vec<u8> = BASE64_STANDARD.decode(key["x5c"][0].as_str().unwrap()).unwrap();
+            assert_eq!(key["x5t"], base64_url::encode(&openssl::sha::sha1(&der)));
+            assert_eq!(key["x5t#S256"], base64_url::encode(&openssl::sha::sha256(&der)));
+        }
+
+        // Thumbprints only depend on the key
+        let jwk: &Jwk = &jwks.keys[0];
+        let reloaded: Jwk = Jwk::from_pem(jwk.private_key_pem(), None, "kid".to_string()).unwrap();
+        assert_eq!(reloaded.thumbprint().unwrap(), jwk.kid());
+        assert!(JwksStore::new(&JwksConfig::default(), vec![Algorithm::RS256])
+            .unwrap()
+            .get()
+            .unwrap()
+            .keys
+            .iter()
+            .all(|jwk| uuid::Uuid::parse_str(jwk.kid()).is_ok()));
+    }
+
+    #[test]
+    fn ed25519_keys_with_leading_zeros_round_trip_test() {
+        let mut raw_key: [u8; 32] = [7; 32];
+        raw_key[0] = 0;
+        let key_pair = openssl::pkey::PKey::private_key_from_raw_bytes(&raw_key, openssl::pkey::Id::ED25519).unwrap();
+        let jwk: Jwk = Jwk::from_key_pair(&key_pair, None, "kid".to_string(), None).unwrap();
+
+        let reloaded: Jwk = Jwk::from_private_jwk(&jwk.to_private_jwk().unwrap(), None, None).unwrap();
+        assert_eq!(reloaded.private_key_pem(), jwk.private_key_pem());
+        assert_eq!(reloaded.kid(), "kid");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn persisted_keys_are_readable_by_the_owner_only_test() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let directory = temp_dir();
+        let config: JwksConfig = toml::from_str(&format!("directory = {directory:?}")).unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&config, vec![Algorithm::ES256]).unwrap();
+        jwk_store.rotate_keys().unwrap();
+
+        let mode: u32 = std::fs::metadata(directory.join("jwks.json"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("localauth0-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn persisted_keys_survive_restarts_test() {
+        let directory = temp_dir();
+        let config: JwksConfig = toml::from_str(&format!(
+            "signing_algs = [\"RS256\", \"ES384\", \"EdDSA\"]\ndirectory = {directory:?}"
+        ))
+        .unwrap();
+        let algs: Vec<Algorithm> = config.signing_algs().clone();
+
+        let published = |jwk_store: &JwksStore| serde_json::to_value(jwk_store.get().unwrap()).unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&config, algs.clone()).unwrap();
+        let before: serde_json::Value = published(&jwk_store);
+        assert!(directory.join("jwks.json").exists());
+
+        let restarted: JwksStore = JwksStore::new(&config, algs.clone()).unwrap();
+        assert_eq!(published(&restarted), before);
+        assert_eq!(
+            restarted.signing_jwk().unwrap().kid(),
+            jwk_store.signing_jwk().unwrap().kid()
+        );
+
+        let claims: Claims = Claims::new(
+            "audience".to_string(),
+            CLIENT_ID_VALUE.to_string(),
+            vec![],
+            "issuer".to_string(),
+            "subject".to_string(),
+            GrantType::ClientCredentials,
+            vec![],
+        );
+        let jwt: String = jwk_store
+            .signing_jwk_for(Algorithm::ES384)
+            .unwrap()
+            .encode(&claims)
+            .unwrap();
+        assert!(restarted.get().unwrap().parse::<Claims>(&jwt, &["audience"]).is_ok());
+
+        restarted.rotate_keys().unwrap();
+        let rotated: serde_json::Value = published(&restarted);
+        assert_ne!(rotated, before);
+        assert_eq!(published(&JwksStore::new(&config, algs).unwrap()), rotated);
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn configured_keys_are_loaded_test() {
+        let directory = temp_dir();
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let pem_key: Jwk = Jwk::new(Algorithm::ES256, KidFormat::Uuid).unwrap();
+        std::fs::write(directory.join("pem-key.pem"), pem_key.private_key_pem()).unwrap();
+        let json_key: Jwk = Jwk::new(Algorithm::EdDSA, KidFormat::Uuid).unwrap();
+        std::fs::write(
+            directory.join("json-key.json"),
+            serde_json::to_vec(&json_key.to_private_jwk().unwrap()).unwrap(),
+        )
+        .unwrap();
+        let inline_key: Jwk = Jwk::new(Algorithm::RS256, KidFormat::Uuid).unwrap();
+
+        let config: JwksConfig = toml::from_str(&format!(
+            r#"
+                signing_algs = ["PS256", "ES256", "EdDSA"]
+
+                [[key]]
+                path = {:?}
+
+                [[key]]
+                path = {:?}
+
+                [[key]]
+                kid = "inline-pem"
+                alg = "PS256"
+                pem = {:?}
+            "#,
+            directory.join("pem-key.pem"),
+            directory.join("json-key.json"),
+            String::from_utf8(inline_key.private_key_pem().clone()).unwrap(),
+        ))
+        .unwrap();
+        let jwk_store: JwksStore = JwksStore::new(&config, config.signing_algs().clone()).unwrap();
+
+        let jwks: Jwks = jwk_store.get().unwrap();
+        // Configured keys become the current ones, next keys are generated
+        assert_eq!(jwks.keys.len(), 6);
+        let keys: Vec<(&str, &str)> = jwks.keys[..3].iter().map(|jwk| (jwk.kid(), jwk.alg())).collect();
+        assert_eq!(
+            keys,
+            vec![("pem-key", "ES256"), (json_key.kid(), "EdDSA"), ("inline-pem", "PS256")]
+        );
+        assert_eq!(jwks.keys[0].private_key_pem(), pem_key.private_key_pem());
+        assert_eq!(jwks.keys[1].private_key_pem(), json_key.private_key_pem());
+        assert_eq!(jwks.keys[2].private_key_pem(), inline_key.private_key_pem());
+
+        let inline_jwk: serde_json::Value = serde_json::to_value(pem_key.to_private_jwk().unwrap()).unwrap();
+        let config: JwksConfig = serde_json::from_value(json!({
+            "signing_algs": ["ES256", "RS256"],
+            "key": [{ "kid": "inline-jwk", "jwk": inline_jwk }],
+        }))
+        .unwrap();
+        let jwks: Jwks = JwksStore::new(&config, config.signing_algs().clone())
+            .unwrap()
+            .get()
+            .unwrap();
+        assert_eq!(jwks.keys.len(), 4);
+        assert_eq!(jwks.keys[0].kid(), "inline-jwk");
+        assert_eq!(jwks.keys[0].private_key_pem(), pem_key.private_key_pem());
+        assert_eq!(jwks.keys.iter().filter(|jwk| jwk.alg() == "RS256").count(), 2);
+
+        let mismatching: JwksConfig = serde_json::from_value(json!({
+            "key": [{ "kid": "mismatching", "alg": "ES384", "jwk": inline_jwk }],
+        }))
+        .unwrap();
+        assert!(matches!(
+            JwksStore::new(&mismatching, vec![]),
+            Err(Error::InvalidSigningKey(_))
+        ));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
 }
@@ -1,11 +1,21 @@
+pub use actions::Actions as ActionsStore;
 pub use audiences::Audiences as AudiencesStore;
 pub use authorizations::Authorizations as AuthorizationsStore;
+pub use clients::Clients as ClientsStore;
 pub use custom_claims::CustomClaims as CustomClaimsStore;
 pub use jwks::JwksStore;
+pub use refresh_tokens::RefreshTokens as RefreshTokensStore;
+pub use roles::Roles as RolesStore;
 pub use user_info::UserInfoStore;
+pub use users::Users as UsersStore;
 
+mod actions;
 mod audiences;
 mod authorizations;
+mod clients;
 mod custom_claims;
 mod jwks;
+mod refresh_tokens;
+mod roles;
 mod user_info;
+mod users;
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+use std::sync::{RwLock, RwLockWriteGuard};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::RefreshTokenConfig;
+use crate::error::Error;
+use crate::model::RefreshToken;
+
+const INVALID_REFRESH_TOKEN: &str = "Unknown or invalid refresh token.";
+
+pub struct RefreshTokens {
+    cache: RwLock<HashMap<String, RefreshToken>>,
+    rotation: bool,
+    absolute_lifetime: Duration,
+    inactivity_lifetime: Duration,
+    reuse_interval: Duration,
+}
+
+impl RefreshTokens {
+    pub fn new(config: &RefreshTokenConfig) -> Self {
+        Self {
+            cache: RwLock::new(HashMap::new()),
+            rotation: *config.rotation(),
+            absolute_lifetime: Duration::seconds(*config.absolute_lifetime()),
+            inactivity_lifetime: Duration::seconds(*config.inactivity_lifetime()),
+            reuse_interval: Duration::seconds(*config.reuse_interval()),
+        }
+    }
+
+    pub fn rotation(&self) -> bool {
+        self.rotation
+    }
+
+    /// Issue the first refresh token of a new family
+    pub fn issue(
+        &self,
+        client_id: &str,
+        audience: &str,
+        scope: Option<String>,
+        subject: Option<String>,
+    ) -> Result<RefreshToken, Error> {
+        let refresh_token: RefreshToken =
+            RefreshToken::new(client_id.to_string(), audience.to_string(), scope, subject);
+
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(refresh_token.token().to_string(), refresh_token.clone());
+
+        Ok(refresh_token)
+    }
+
+    /// Exchange the given refresh token. When rotation is enabled the given token gets invalidated and a new token
+    /// of the same family is returned, otherwise the same token is returned.
+    ///
+    /// Exchanging an already rotated token outside the reuse interval is considered a reuse: the whole family gets
+    /// revoked, so that even the latest token issued to the legitimate client stops working.
+    pub fn exchange(&self, token: &str, client_id: &str) -> Result<RefreshToken, Error> {
+        let mut cache: RwLockWriteGuard<HashMap<String, RefreshToken>> =
+            self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        let now: DateTime<Utc> = Utc::now();
+        let mut refresh_token: RefreshToken = self.validate(&mut cache, token, client_id, now)?;
+
+        if self.rotation {
+            let next: RefreshToken = refresh_token.rotate();
+
+            if refresh_token.rotated_at().is_none() {
+                refresh_token.set_rotated_at(now);
+            }
+
+            cache.insert(token.to_string(), refresh_token);
+            cache.insert(next.token().to_string(), next.clone());
+            Ok(next)
+        } else {
+            refresh_token.set_last_used_at(now);
+            cache.insert(token.to_string(), refresh_token.clone());
+            Ok(refresh_token)
+        }
+    }
+
+    /// Checks the given refresh token could be exchanged, without exchanging it: the token is left untouched until
+    /// the new tokens have been issued. Reuses and expired tokens are revoked as on exchange.
+    pub fn verify(&self, token: &str, client_id: &str) -> Result<RefreshToken, Error> {
+        let mut cache: RwLockWriteGuard<HashMap<String, RefreshToken>> =
+            self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        self.validate(&mut cache, token, client_id, Utc::now())
+    }
+
+    fn validate(
+        &self,
+        cache: &mut HashMap<String, RefreshToken>,
+        token: &str,
+        client_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<RefreshToken, Error> {
+        let refresh_token: RefreshToken = match cache.get(token) {
+            Some(refresh_token) if refresh_token.client_id() == client_id => refresh_token.clone(),
+            _ => return Err(Error::InvalidGrant(INVALID_REFRESH_TOKEN.to_string())),
+        };
+
+        if let Some(rotated_at) = refresh_token.rotated_at() {
+            if now - *rotated_at > self.reuse_interval {
+                let family: String = refresh_token.family().to_string();
+                cache.retain(|_, refresh_token| refresh_token.family() != family);
+                return Err(Error::InvalidGrant(INVALID_REFRESH_TOKEN.to_string()));
+            }
+        }
+
+        if now - *refresh_token.family_created_at() > self.absolute_lifetime
+            || now - *refresh_token.last_used_at() > self.inactivity_lifetime
+        {
+            cache.remove(token);
+            return Err(Error::InvalidGrant(INVALID_REFRESH_TOKEN.to_string()));
+        }
+
+        Ok(refresh_token)
+    }
+
+    /// Removes the refresh tokens of the families past their absolute lifetime
+    pub fn purge_expired(&self) -> Result<(), Error> {
+        let now: DateTime<Utc> = Utc::now();
+
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .retain(|_, refresh_token| now - *refresh_token.family_created_at() <= self.absolute_lifetime);
+
+        Ok(())
+    }
+
+    /// Revokes every refresh token issued to the given user
+    pub fn revoke_for_subject(&self, subject: &str) -> Result<(), Error> {
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .retain(|_, refresh_token| refresh_token.subject() != Some(subject));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::RefreshTokenConfig;
+    use crate::error::Error;
+    use crate::store::RefreshTokensStore;
+
+    fn new_store(config: &str) -> RefreshTokensStore {
+        let config: RefreshTokenConfig = toml::from_str(config).unwrap();
+        RefreshTokensStore::new(&config)
+    }
+
+    #[test]
+    fn refresh_token_is_rotated_on_exchange() {
+        let store = new_store("");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+
+        let second = store.exchange(first.token(), "client_id").unwrap();
+        assert_ne!(first.token(), second.token());
+        assert_eq!(first.family(), second.family());
+        assert_eq!(second.audience(), "audience");
+
+        let third = store.exchange(second.token(), "client_id").unwrap();
+        assert_ne!(second.token(), third.token());
+    }
+
+    #[test]
+    fn reusing_a_rotated_refresh_token_revokes_the_whole_family() {
+        let store = new_store("");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        let second = store.exchange(first.token(), "client_id").unwrap();
+
+        let result = store.exchange(first.token(), "client_id");
+        assert!(matches!(result, Err(Error::InvalidGrant(_))));
+
+        let result = store.exchange(second.token(), "client_id");
+        assert!(matches!(result, Err(Error::InvalidGrant(_))));
+    }
+
+    #[test]
+    fn rotated_refresh_token_can_be_reused_within_reuse_interval() {
+        let store = new_store("reuse_interval = 60");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        let second = store.exchange(first.token(), "client_id").unwrap();
+        let third = store.exchange(first.token(), "client_id").unwrap();
+
+        assert_eq!(second.family(), third.family());
+        assert!(store.exchange(second.token(), "client_id").is_ok());
+    }
+
+    #[test]
+    fn refresh_token_is_not_rotated_when_rotation_is_disabled() {
+        let store = new_store("rotation = false");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+
+        let second = store.exchange(first.token(), "client_id").unwrap();
+        assert_eq!(first.token(), second.token());
+        assert!(store.exchange(first.token(), "client_id").is_ok());
+    }
+
+    #[test]
+    fn expired_refresh_token_is_rejected() {
+        let store = new_store("absolute_lifetime = -1");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        assert!(matches!(
+            store.exchange(first.token(), "client_id"),
+            Err(Error::InvalidGrant(_))
+        ));
+
+        let store = new_store("inactivity_lifetime = -1");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        assert!(matches!(
+            store.exchange(first.token(), "client_id"),
+            Err(Error::InvalidGrant(_))
+        ));
+    }
+
+    #[test]
+    fn verified_refresh_token_is_left_untouched() {
+        let store = new_store("");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+
+        assert_eq!(store.verify(first.token(), "client_id").unwrap().token(), first.token());
+        assert!(store.verify(first.token(), "another_client_id").is_err());
+
+        // Still the latest token of the family, not a reuse
+        let second = store.exchange(first.token(), "client_id").unwrap();
+        assert!(store.exchange(second.token(), "client_id").is_ok());
+    }
+
+    #[test]
+    fn expired_refresh_tokens_are_purged() {
+        let store = new_store("absolute_lifetime = -1");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        store.issue("client_id", "audience", None, None).unwrap();
+
+        store.purge_expired().unwrap();
+        assert!(store.cache.read().unwrap().is_empty());
+        assert!(matches!(
+            store.exchange(first.token(), "client_id"),
+            Err(Error::InvalidGrant(_))
+        ));
+
+        let store = new_store("");
+        store.issue("client_id", "audience", None, None).unwrap();
+        store.purge_expired().unwrap();
+        assert_eq!(store.cache.read().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn refresh_token_issued_to_another_client_is_rejected() {
+        let store = new_store("");
+        let first = store.issue("client_id", "audience", None, None).unwrap();
+        assert!(matches!(
+            store.exchange(first.token(), "another_client_id"),
+            Err(Error::InvalidGrant(_))
+        ));
+    }
+}
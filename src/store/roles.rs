@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::config::RoleConfig;
+use crate::error::Error;
+use crate::model::Role;
+
+pub struct Roles {
+    cache: RwLock<HashMap<String, Role>>,
+}
+
+impl Roles {
+    pub fn new(roles: &[RoleConfig]) -> Self {
+        let map: HashMap<String, Role> = roles
+            .iter()
+            .map(Role::from)
+            .map(|role| (role.name().to_string(), role))
+            .collect();
+
+        Self {
+            cache: RwLock::new(map),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Option<Role>, Error> {
+        Ok(self.cache.read().unwrap_or_else(|p| p.into_inner()).get(name).cloned())
+    }
+
+    /// The roles with the given names. Unknown names are skipped.
+    pub fn get_all(&self, names: &[String]) -> Result<Vec<Role>, Error> {
+        let cache = self.cache.read().unwrap_or_else(|p| p.into_inner());
+
+        Ok(names.iter().filter_map(|name| cache.get(name)).cloned().collect())
+    }
+
+    /// The first of the given names that doesn't match any role
+    pub fn find_unknown(&self, names: &[String]) -> Result<Option<String>, Error> {
+        let cache = self.cache.read().unwrap_or_else(|p| p.into_inner());
+
+        Ok(names.iter().find(|name| !cache.contains_key(*name)).cloned())
+    }
+
+    /// All the roles, sorted by name
+    pub fn all(&self) -> Result<Vec<Role>, Error> {
+        let mut roles: Vec<Role> = self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .cloned()
+            .collect();
+
+        roles.sort_by(|a, b| a.name().cmp(b.name()));
+
+        Ok(roles)
+    }
+
+    /// Creates the role or replaces the one with the same name
+    pub fn put(&self, role: Role) -> Result<(), Error> {
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(role.name().to_string(), role);
+
+        Ok(())
+    }
+
+    pub fn remove(&self, name: &str) -> Result<Option<Role>, Error> {
+        Ok(self.cache.write().unwrap_or_else(|p| p.into_inner()).remove(name))
+    }
+}
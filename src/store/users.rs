@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+use crate::config::UserConfig;
+use crate::error::Error;
+use crate::model::User;
+
+pub struct Users {
+    cache: RwLock<HashMap<String, User>>,
+}
+
+impl Users {
+    pub fn new(users: &[UserConfig]) -> Self {
+        let map: HashMap<String, User> = users
+            .iter()
+            .map(User::from)
+            .map(|user| (user.subject().to_string(), user))
+            .collect();
+
+        Self {
+            cache: RwLock::new(map),
+        }
+    }
+
+    pub fn get(&self, subject: &str) -> Result<Option<User>, Error> {
+        Ok(self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .get(subject)
+            .cloned())
+    }
+
+    /// Adds the given user, failing if a user with the same subject already exists
+    pub fn create(&self, user: User) -> Result<(), Error> {
+        let mut cache = self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        if cache.contains_key(user.subject()) {
+            return Err(Error::Conflict("The user already exists.".to_string()));
+        }
+
+        cache.insert(user.subject().to_string(), user);
+
+        Ok(())
+    }
+
+    /// Replaces the user with the same subject
+    pub fn put(&self, user: User) -> Result<(), Error> {
+        self.cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .insert(user.subject().to_string(), user);
+
+        Ok(())
+    }
+
+    pub fn remove(&self, subject: &str) -> Result<Option<User>, Error> {
+        Ok(self.cache.write().unwrap_or_else(|p| p.into_inner()).remove(subject))
+    }
+
+    /// Users with the given email, compared case insensitively
+    pub fn find_by_email(&self, email: &str) -> Result<Vec<User>, Error> {
+        Ok(self
+            .all()?
+            .into_iter()
+            .filter(|user| user.user_info().email.eq_ignore_ascii_case(email))
+            .collect())
+    }
+
+    /// Assigns the given roles to the user, returning it updated or `None` if it doesn't exist
+    pub fn assign_roles(&self, subject: &str, roles: Vec<String>) -> Result<Option<User>, Error> {
+        let mut cache = self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        Ok(cache.get_mut(subject).map(|user| {
+            user.assign_roles(roles);
+            user.clone()
+        }))
+    }
+
+    /// Removes the given roles from the user, returning it updated or `None` if it doesn't exist
+    pub fn remove_roles(&self, subject: &str, roles: &[String]) -> Result<Option<User>, Error> {
+        let mut cache = self.cache.write().unwrap_or_else(|p| p.into_inner());
+
+        Ok(cache.get_mut(subject).map(|user| {
+            user.remove_roles(roles);
+            user.clone()
+        }))
+    }
+
+    /// Renames the given role for every user it is assigned to
+    pub fn rename_role(&self, role: &str, name: &str) -> Result<(), Error> {
+        for user in self
+            .cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .values_mut()
+            .filter(|user| user.roles().iter().any(|assigned| assigned == role))
+        {
+            let roles: Vec<String> = user
+                .roles()
+                .iter()
+                .map(|assigned| {
+                    if assigned == role {
+                        name.to_string()
+                    } else {
+                        assigned.clone()
+                    }
+                })
+                .collect();
+            user.set_roles(roles);
+        }
+
+        Ok(())
+    }
+
+    /// Removes the given role from every user it is assigned to
+    pub fn unassign_role(&self, role: &str) -> Result<(), Error> {
+        for user in self
+            .cache
+            .write()
+            .unwrap_or_else(|p| p.into_inner())
+            .values_mut()
+            .filter(|user| user.roles().iter().any(|assigned| assigned == role))
+        {
+            user.remove_roles(&[role.to_string()]);
+        }
+
+        Ok(())
+    }
+
+    /// All the users, sorted by name
+    pub fn all(&self) -> Result<Vec<User>, Error> {
+        let mut users: Vec<User> = self
+            .cache
+            .read()
+            .unwrap_or_else(|p| p.into_inner())
+            .values()
+            .cloned()
+            .collect();
+
+        users.sort_by(|a, b| a.user_info().name.cmp(&b.user_info().name));
+
+        Ok(users)
+    }
+}
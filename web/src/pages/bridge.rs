@@ -8,7 +8,7 @@ use wasm_bindgen_futures::spawn_local;
 use yew::html::Scope;
 use yew::{Component, Context};
 
-use crate::pages::model::{Jwt, PermissionsForAudience, TokenRequest, LoginRequest, LoginResponse};
+use crate::pages::model::{Jwt, PermissionsForAudience, TokenRequest, LoginRequest, LoginResponse, User};
 
 pub fn generate_token<T, F>(ctx: &Context<T>, msg: F, audience: String)
 where
@@ -81,14 +81,34 @@ where
     });
 }
 
-pub fn login<T, F>(ctx: &Context<T>, msg: F, audience: String)
+pub fn get_users<T, F>(ctx: &Context<T>, msg: F)
+where
+    T: Component,
+    F: 'static + FnOnce(Vec<User>) -> T::Message,
+{
+    let link: Scope<T> = ctx.link().clone();
+    spawn_local(async move {
+        let users: Vec<User> = Request::get("/users")
+            .header("Content-type", "application/json")
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+
+        link.send_message(msg(users))
+    });
+}
+
+pub fn login<T, F>(ctx: &Context<T>, msg: F, request: LoginRequest)
 where
     T: Component,
     F: 'static + FnOnce(String) -> T::Message,
 {
     let link: Scope<T> = ctx.link().clone();
     spawn_local(async move {
-        let body: String = serde_json::to_string(&LoginRequest::new(audience)).unwrap();
+        let body: String = serde_json::to_string(&request).unwrap();
 
         let response: LoginResponse = Request::post("/oauth/login")
             .header("Content-type", "application/json")
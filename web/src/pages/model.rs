@@ -3,12 +3,17 @@ use serde::Deserialize;
 #[derive(Deserialize, Debug, Clone)]
 pub struct Jwt {
     access_token: String,
+    expires_in: i64,
 }
 
 impl Jwt {
     pub fn access_token(&self) -> &str {
         &self.access_token
     }
+
+    pub fn expires_in(&self) -> i64 {
+        self.expires_in
+    }
 }
 
 #[derive(serde::Serialize)]
@@ -45,12 +50,26 @@ impl PermissionsForAudience {
 #[derive(serde::Serialize)]
 pub struct LoginRequest {
     pub audience: String,
+    pub client_id: String,
+    pub redirect_uri: String,
+    pub scope: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_challenge: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_challenge_method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
 }
 
-impl LoginRequest {
-    pub fn new(audience: String) -> Self {
-        Self { audience }
-    }
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct User {
+    pub sub: String,
+    pub name: String,
+    pub email: String,
 }
 
 #[derive(Deserialize)]
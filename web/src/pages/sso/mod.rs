@@ -7,14 +7,14 @@ use yew::{Component, Context, html, Html};
 use yew_router::prelude::RouterScopeExt;
 
 use crate::pages::{bindgen, bridge};
-use crate::pages::model::Jwt;
+use crate::pages::model::{Jwt, LoginRequest, User};
 
 mod msg;
 
 const MISSING_PARAMS_CONTENT: &str = "Bad request while authenticating with sso:\
 Missing some query params.\
 Mandatory query params are: `client_id`, audience`, `redirect_uri`, `scope` and `response_type`\
-Optional query params are: `connection`, `state` and `bypass`";
+Optional query params are: `connection`, `state`, `nonce`, `code_challenge`, `code_challenge_method`, `login_hint` and `bypass`";
 
 #[derive(Deserialize, Debug)]
 #[allow(dead_code)]
@@ -26,18 +26,25 @@ struct QueryParams {
     scope: String,
     response_type: String,
     state: Option<String>,
+    nonce: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+    // Subject of the user to log in as
+    login_hint: Option<String>,
     bypass: Option<bool>,
 }
 
 enum View<'a> {
     Token(Url),
-    Code(&'a Context<SSO>),
+    Code(&'a Context<SSO>, &'a [User]),
 }
 
 pub struct SSO {
     query_params_opt: Option<QueryParams>,
     token: Option<Jwt>,
     code: Option<String>,
+    users: Vec<User>,
+    user: Option<String>,
     login_pressed: bool,
 }
 
@@ -51,10 +58,14 @@ impl Component for SSO {
             .location()
             .and_then(|location| location.query::<QueryParams>().ok());
 
+        let () = bridge::get_users(ctx, Msg::UsersReceived);
+
         Self {
+            user: query_params_opt.as_ref().and_then(|params| params.login_hint.clone()),
             query_params_opt,
             token: None,
             code: None,
+            users: vec![],
             login_pressed: false,
         }
     }
@@ -69,7 +80,12 @@ impl Component for SSO {
                 self.code = Some(code);
                 true
             }
-            Msg::LoginPressed => {
+            Msg::UsersReceived(users) => {
+                self.users = users;
+                true
+            }
+            Msg::LoginPressed(user) => {
+                self.user = user;
                 self.login_pressed = true;
                 true
             }
@@ -101,14 +117,14 @@ impl Component for SSO {
                             html! { <div></div> }
                         }
                         None if Some(true) == query_params.bypass => {
-                            let () = bridge::login(ctx, |code| Msg::CodeReceived(code), query_params.audience.clone());
+                            let () = bridge::login(ctx, |code| Msg::CodeReceived(code), login_request(query_params, self.user.clone()));
                             html! { <div>{"Loading.."}</div>}
                         }
                         None if self.login_pressed => {
-                            let () = bridge::login(ctx, |code| Msg::CodeReceived(code), query_params.audience.clone());
+                            let () = bridge::login(ctx, |code| Msg::CodeReceived(code), login_request(query_params, self.user.clone()));
                             html! { <div>{"Loading.."}</div>}
                         }
-                        None => login_view(View::Code(ctx)),
+                        None => login_view(View::Code(ctx, &self.users)),
                     },
                     Ok(url) => match self.token.clone() {
                         None => {
@@ -152,9 +168,19 @@ fn button_for_view(view: View) -> Html {
         View::Token(url) => html! {
             <a class="button is-large is-responsive is-success is-light is-outlined" type="button" href={url.to_string()}>{"Login"}</a>
         },
-        View::Code(ctx) => html! {
-            // When users are supported this view can collect credentials to forward to the backend, but currently no credentials are required.
-            <a class="button is-large is-responsive is-success is-light is-outlined" type="button" onclick={ctx.link().callback(|_|Msg::LoginPressed)}>{"Login"}</a>
+        View::Code(ctx, []) => html! {
+            // Without configured users the default one is logged in
+            <a class="button is-large is-responsive is-success is-light is-outlined" type="button" onclick={ctx.link().callback(|_|Msg::LoginPressed(None))}>{"Login"}</a>
+        },
+        View::Code(ctx, users) => html! {
+            <div class="buttons is-centered">
+                { for users.iter().map(|user| {
+                    let sub: String = user.sub.clone();
+                    html! {
+                        <a class="button is-large is-responsive is-success is-light is-outlined" type="button" title={user.email.clone()} onclick={ctx.link().callback(move |_| Msg::LoginPressed(Some(sub.clone())))}>{format!("Login as {}", user.name)}</a>
+                    }
+                }) }
+            </div>
         }
     }
 }
@@ -165,12 +191,27 @@ fn error_page(message: &str) -> Html {
     }
 }
 
+fn login_request(query_params: &QueryParams, user: Option<String>) -> LoginRequest {
+    LoginRequest {
+        audience: query_params.audience.clone(),
+        client_id: query_params.client_id.clone(),
+        redirect_uri: query_params.redirect_uri.clone(),
+        scope: query_params.scope.clone(),
+        nonce: query_params.nonce.clone(),
+        state: query_params.state.clone(),
+        code_challenge: query_params.code_challenge.clone(),
+        code_challenge_method: query_params.code_challenge_method.clone(),
+        user,
+    }
+}
+
 fn build_token_url(state_opt: Option<&String>, mut url: Url, token: Jwt) -> Url {
     let state: String = state_opt.map(|state| format!("&state={}", state)).unwrap_or_default();
 
     let access_token: String = format!(
-        "access_token={}&token_type=Bearer&expires_in=3600{}",
+        "access_token={}&token_type=Bearer&expires_in={}{}",
         token.access_token(),
+        token.expires_in(),
         state
     );
 
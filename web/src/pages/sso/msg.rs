@@ -1,8 +1,9 @@
-use crate::pages::model::Jwt;
+use crate::pages::model::{Jwt, User};
 
 #[derive(Debug)]
 pub enum Msg {
     TokenReceived(Jwt),
     CodeReceived(String),
-    LoginPressed,
+    UsersReceived(Vec<User>),
+    LoginPressed(Option<String>),
 }